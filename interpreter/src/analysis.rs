@@ -0,0 +1,554 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::{ast::Ast, environment::LocalEnvironment, optimize::PURE_BUILTINS};
+
+/// Kind of a finding from the static analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisKind {
+    // The AST could not be compiled, nothing else is analyzed
+    InvalidAst,
+    // A function calls itself outside of a tail position
+    NonTailRecursion,
+    // A let binding is never referenced
+    UnusedBinding,
+    // An identifier that is not bound anywhere it could be looked up
+    UndefinedIdentifier,
+    // An expression without side effects in a block whose value is not the block's result
+    DiscardedValue,
+}
+
+/// A finding from analyzing a program before running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub kind: AnalysisKind,
+    pub message: String,
+    // Span of the source (start, length), None unless the parser added spans to the JSON AST
+    pub span: Option<Span>,
+}
+
+/// Byte offset and length of an expression in the source
+pub type Span = (usize, usize);
+
+impl Analysis {
+    fn new(kind: AnalysisKind, message: String, span: Option<Span>) -> Self {
+        Self {
+            kind,
+            message,
+            span,
+        }
+    }
+}
+
+/// Analyzes a JSON AST without running it, assuming lexical scope
+/// Reports non-tail recursive calls, unused let bindings, references to undefined identifiers,
+/// and expressions in blocks with no effect since their value is discarded
+/// Findings have the span of the expression they are about if the parser added a "span" to it
+pub fn analyze(val: &Value) -> Vec<Analysis> {
+    let ast = match Ast::from_value(val) {
+        Ok(ast) => ast,
+        Err(e) => return vec![Analysis::new(AnalysisKind::InvalidAst, e.to_string(), None)],
+    };
+
+    let mut analyzer = Analyzer {
+        findings: vec![],
+        builtins: LocalEnvironment::default_environment(),
+        scopes: vec![],
+        lambda_depth: 0,
+    };
+    analyzer.visit(&ast, Some(val));
+    analyzer.findings
+}
+
+/// Value at a JSON pointer into the JSON AST an expression was compiled from, if it is known
+fn at<'v>(val: Option<&'v Value>, pointer: &str) -> Option<&'v Value> {
+    val.and_then(|val| val.pointer(pointer))
+}
+
+/// Elements of a JSON array, or none if the value is not known
+fn elements(val: Option<&Value>) -> &[Value] {
+    val.and_then(Value::as_array).map_or(&[], Vec::as_slice)
+}
+
+/// The "span" ([offset, length]) of a JSON AST object
+fn span(val: Option<&Value>) -> Option<Span> {
+    match at(val, "/span")?.as_array()?.as_slice() {
+        [offset, length] => Some((offset.as_u64()? as usize, length.as_u64()? as usize)),
+        _ => None,
+    }
+}
+
+/// A name bound in a scope
+struct Binding {
+    name: String,
+    // Only let bindings are reported when unused
+    is_let: bool,
+    used: bool,
+    // Span of the expression making the binding
+    span: Option<Span>,
+}
+
+/// Bindings of one local environment
+#[derive(Default)]
+struct Scope {
+    bindings: Vec<Binding>,
+    // Every name bound directly in this scope, including bindings made after the current expression
+    // A function body runs later, so it can see bindings (like a recursive def) made after the function is created
+    all_names: HashSet<String>,
+    // Names used by function bodies through all_names before they were bound
+    deferred_uses: HashSet<String>,
+}
+
+struct Analyzer {
+    findings: Vec<Analysis>,
+    builtins: LocalEnvironment,
+    scopes: Vec<Scope>,
+    // How many function bodies are being visited
+    lambda_depth: usize,
+}
+
+impl Analyzer {
+    /// Visits an expression, along with the JSON AST it was compiled from if known to find spans
+    fn visit(&mut self, ast: &Ast, val: Option<&Value>) {
+        match ast {
+            Ast::Integer(_)
+            | Ast::Float(_)
+            | Ast::Boolean(_)
+            | Ast::String(_)
+            | Ast::Char(_)
+            | Ast::Value(_) => {}
+            Ast::List(list) => self.visit_all(list, elements(val)),
+            Ast::Application(list) => self.visit_all(list, elements(at(val, "/Application"))),
+            Ast::Identifier(name) => self.resolve(name, span(val)),
+            Ast::Block(block) => self.visit_block(block, elements(at(val, "/Block")), vec![]),
+            Ast::Lambda {
+                params, block, doc, ..
+            } => {
+                // The doc string is not part of the compiled block
+                let vals = elements(at(val, "/Lambda/1/Block"));
+                let vals = vals.get(usize::from(doc.is_some())..).unwrap_or_default();
+                self.lambda_depth += 1;
+                let params = params
+                    .iter()
+                    .map(|param| (param.clone(), false, None))
+                    .collect();
+                self.visit_block(block, vals, params);
+                self.lambda_depth -= 1;
+            }
+            Ast::Cond(clauses) => self.visit_clauses(clauses, elements(at(val, "/Cond"))),
+            Ast::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.visit(value, at(val, "/Switch/0"));
+                self.visit_clauses(cases, elements(at(val, "/Switch/1")));
+                if let Some(default) = default {
+                    self.visit(default, at(val, "/Switch/2"));
+                }
+            }
+            Ast::Let { name, value, block } | Ast::Def { name, value, block } => {
+                let is_let = matches!(ast, Ast::Let { .. });
+                let key = if is_let { "Let" } else { "Def" };
+                if !is_let {
+                    if let Ast::Lambda { params, block, .. } = value.as_ref() {
+                        self.check_recursion(name, params, block, span(val));
+                    }
+                }
+
+                self.visit(value, at(val, &format!("/{key}/1")));
+                match block {
+                    Some(block) => self.visit_block(
+                        block,
+                        elements(at(val, &format!("/{key}/2/Block"))),
+                        vec![(name.clone(), is_let, span(val))],
+                    ),
+                    None => self.bind(name, is_let, span(val)),
+                }
+            }
+            Ast::Const { name, value } => {
+                self.visit(value, at(val, "/Const/1"));
+                self.bind(name, false, span(val));
+            }
+            Ast::Destructure { names, value } => {
+                self.visit(value, at(val, "/Destructure/1"));
+                names
+                    .iter()
+                    .for_each(|name| self.bind(name, true, span(val)));
+            }
+            Ast::LetIn { name, value, body } => {
+                self.visit(value, at(val, "/LetIn/1"));
+                self.visit_block(
+                    std::slice::from_ref(body),
+                    at(val, "/LetIn/2").map_or(&[], std::slice::from_ref),
+                    vec![(name.clone(), true, span(val))],
+                );
+            }
+            Ast::Try {
+                body,
+                name,
+                handler,
+            } => {
+                self.visit_block(body, elements(at(val, "/Try/0/Block")), vec![]);
+                self.visit_block(
+                    handler,
+                    elements(at(val, "/Try/2/Block")),
+                    vec![(name.clone(), false, None)],
+                );
+            }
+            Ast::Assignment { name, value } => {
+                self.visit(value, at(val, "/Assignment/1"));
+                self.resolve(name, span(val));
+            }
+        }
+    }
+
+    /// Visits each expression with the JSON value at the same position, if there is one
+    fn visit_all(&mut self, list: &[Ast], vals: &[Value]) {
+        for (i, ast) in list.iter().enumerate() {
+            self.visit(ast, vals.get(i));
+        }
+    }
+
+    /// Visits the condition and expression of each clause
+    fn visit_clauses(&mut self, clauses: &[(Ast, Ast)], vals: &[Value]) {
+        for (i, (condition, expr)) in clauses.iter().enumerate() {
+            let clause = vals.get(i);
+            self.visit(condition, at(clause, "/Clause/0"));
+            self.visit(expr, at(clause, "/Clause/1"));
+        }
+    }
+
+    /// Visits expressions in a new scope, starting with the given (name, is_let, span) bindings
+    fn visit_block(
+        &mut self,
+        block: &[Ast],
+        vals: &[Value],
+        bindings: Vec<(String, bool, Option<Span>)>,
+    ) {
+        let mut scope = Scope::default();
+        for ast in block {
+            if let Ast::Let { name, .. } | Ast::Def { name, .. } | Ast::Const { name, .. } = ast {
+                scope.all_names.insert(name.clone());
+            }
+            if let Ast::Destructure { names, .. } = ast {
+                scope.all_names.extend(names.iter().cloned());
+            }
+        }
+        self.scopes.push(scope);
+
+        for (name, is_let, span) in bindings {
+            self.bind(&name, is_let, span);
+        }
+        for (i, ast) in block.iter().enumerate() {
+            if i + 1 < block.len() && self.is_pure(ast) {
+                self.findings.push(Analysis::new(
+                    AnalysisKind::DiscardedValue,
+                    discarded_message(ast),
+                    span(vals.get(i)),
+                ));
+            }
+            self.visit(ast, vals.get(i));
+        }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        for binding in scope.bindings {
+            if binding.is_let && !binding.used && !scope.deferred_uses.contains(&binding.name) {
+                self.findings.push(Analysis::new(
+                    AnalysisKind::UnusedBinding,
+                    format!("'{}' is bound by let but never used", binding.name),
+                    binding.span,
+                ));
+            }
+        }
+    }
+
+    fn bind(&mut self, name: &str, is_let: bool, span: Option<Span>) {
+        if self.scopes.is_empty() {
+            self.scopes.push(Scope::default());
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.all_names.insert(name.to_string());
+            scope.bindings.push(Binding {
+                name: name.to_string(),
+                is_let,
+                used: false,
+                span,
+            });
+        }
+    }
+
+    /// Marks the binding an identifier refers to as used, or reports it as undefined
+    fn resolve(&mut self, name: &str, span: Option<Span>) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.bindings.iter_mut().rev().find(|b| b.name == name) {
+                binding.used = true;
+                return;
+            }
+            if self.lambda_depth > 0 && scope.all_names.contains(name) {
+                scope.deferred_uses.insert(name.to_string());
+                return;
+            }
+        }
+
+        if self.builtins.lookup(name).is_none() {
+            self.findings.push(Analysis::new(
+                AnalysisKind::UndefinedIdentifier,
+                format!("'{}' is not defined", name),
+                span,
+            ));
+        }
+    }
+
+    /// Whether evaluating an expression has no effect other than its value
+    /// Only calls of builtins known to be pure count, so print, def, and assignment are never pure
+    fn is_pure(&self, ast: &Ast) -> bool {
+        match ast {
+            Ast::Integer(_)
+            | Ast::Float(_)
+            | Ast::Boolean(_)
+            | Ast::String(_)
+            | Ast::Char(_)
+            | Ast::Identifier(_)
+            | Ast::Lambda { .. }
+            | Ast::Value(_) => true,
+            Ast::List(list) => list.iter().all(|ast| self.is_pure(ast)),
+            Ast::Application(list) => match list.split_first() {
+                Some((Ast::Identifier(func), args)) => {
+                    PURE_BUILTINS.contains(&func.as_str())
+                        && !self.is_bound(func)
+                        && args.iter().all(|ast| self.is_pure(ast))
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a name is bound by the program in any enclosing scope, hiding a builtin
+    fn is_bound(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.all_names.contains(name))
+    }
+
+    /// Reports calls of a function (bound by def) to itself which are not in a tail position
+    fn check_recursion(
+        &mut self,
+        name: &str,
+        params: &[String],
+        block: &[Ast],
+        span: Option<Span>,
+    ) {
+        // A parameter of the same name hides the function
+        if params.iter().any(|param| param == name) {
+            return;
+        }
+        let mut calls = 0;
+        tail_block(name, block, true, &mut calls);
+        if calls > 0 {
+            self.findings.push(Analysis::new(
+                AnalysisKind::NonTailRecursion,
+                format!(
+                    "'{}' calls itself outside of a tail position {} time(s), deep recursion may overflow the stack",
+                    name, calls
+                ),
+                span,
+            ));
+        }
+    }
+}
+
+fn discarded_message(ast: &Ast) -> String {
+    match ast {
+        Ast::Application(list) => match list.first() {
+            Some(Ast::Identifier(func)) => {
+                format!("result of calling '{}' is never used", func)
+            }
+            _ => "result of a call is never used".to_string(),
+        },
+        Ast::Identifier(name) => format!("value of '{}' is never used", name),
+        _ => "value of an expression is never used".to_string(),
+    }
+}
+
+/// Counts the non-tail calls to name in a block, where only the last expression can be in a tail position
+fn tail_block(name: &str, block: &[Ast], tail: bool, calls: &mut usize) {
+    for (i, ast) in block.iter().enumerate() {
+        tail_calls(name, ast, tail && i + 1 == block.len(), calls);
+    }
+}
+
+/// Counts the non-tail calls to name in an expression
+fn tail_calls(name: &str, ast: &Ast, tail: bool, calls: &mut usize) {
+    match ast {
+        Ast::Application(list) => {
+            if !tail && matches!(list.first(), Some(Ast::Identifier(func)) if func == name) {
+                *calls += 1;
+            }
+            list.iter()
+                .for_each(|ast| tail_calls(name, ast, false, calls));
+        }
+        Ast::List(list) => list
+            .iter()
+            .for_each(|ast| tail_calls(name, ast, false, calls)),
+        Ast::Block(block) => tail_block(name, block, tail, calls),
+        Ast::Cond(clauses) => {
+            for (condition, expr) in clauses {
+                tail_calls(name, condition, false, calls);
+                tail_calls(name, expr, tail, calls);
+            }
+        }
+        Ast::Switch {
+            value,
+            cases,
+            default,
+        } => {
+            tail_calls(name, value, false, calls);
+            for (case, expr) in cases {
+                tail_calls(name, case, false, calls);
+                tail_calls(name, expr, tail, calls);
+            }
+            if let Some(default) = default {
+                tail_calls(name, default, tail, calls);
+            }
+        }
+        Ast::Let { value, block, .. } | Ast::Def { value, block, .. } => {
+            tail_calls(name, value, false, calls);
+            if let Some(block) = block {
+                tail_block(name, block, tail, calls);
+            }
+        }
+        Ast::LetIn { value, body, .. } => {
+            tail_calls(name, value, false, calls);
+            tail_calls(name, body, tail, calls);
+        }
+        // The try expression still has to catch errors after the body, so the body is not a tail position
+        Ast::Try { body, handler, .. } => {
+            tail_block(name, body, false, calls);
+            tail_block(name, handler, tail, calls);
+        }
+        Ast::Const { value, .. }
+        | Ast::Assignment { value, .. }
+        | Ast::Destructure { value, .. } => tail_calls(name, value, false, calls),
+        // Calls inside of another function are only made when that function is called
+        Ast::Lambda { .. } => {}
+        Ast::Integer(_)
+        | Ast::Float(_)
+        | Ast::Boolean(_)
+        | Ast::String(_)
+        | Ast::Char(_)
+        | Ast::Identifier(_)
+        | Ast::Value(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<AnalysisKind> {
+        let ast = parser::parse("test", input).expect("Test program should parse");
+        analyze(&ast).into_iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn non_tail_recursion() {
+        assert_eq!(
+            kinds("{ def fact = lambda(n) { cond (zero?(n) => 1) (true => mul(n, fact(sub(n, 1)))) }; fact(5) }"),
+            vec![AnalysisKind::NonTailRecursion]
+        );
+        // Calls in the tail position are fine
+        assert_eq!(
+            kinds("{ def loop = lambda(n, acc) { cond (zero?(n) => acc) (true => loop(sub(n, 1), add(acc, n))) }; loop(5, 0) }"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn unused_binding() {
+        assert_eq!(
+            kinds("{ let a = 1; let b = 2; b }"),
+            vec![AnalysisKind::UnusedBinding]
+        );
+        // A let with its own block (from JSON ASTs) is used inside of it
+        let ast = serde_json::json!({ "Let": [{ "Identifier": "a" }, 1, { "Block": [{ "Identifier": "a" }] }] });
+        assert_eq!(analyze(&ast), vec![]);
+    }
+
+    #[test]
+    fn undefined_identifier() {
+        assert_eq!(
+            kinds("{ let a = 1; add(a, b) }"),
+            vec![AnalysisKind::UndefinedIdentifier]
+        );
+        // Builtins and bindings made later in the enclosing scope are visible in function bodies
+        assert_eq!(
+            kinds("{ def f = lambda() { g() }; def g = lambda() { println(1) }; f() }"),
+            vec![]
+        );
+        // A let with its own block is out of scope once the block ends
+        let ast = serde_json::json!({ "Block": [
+            { "Let": [{ "Identifier": "a" }, 1, { "Block": [{ "Identifier": "a" }] }] },
+            { "Identifier": "a" }
+        ] });
+        let findings = analyze(&ast);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, AnalysisKind::UndefinedIdentifier);
+        assert_eq!(findings[0].message, "'a' is not defined");
+    }
+
+    #[test]
+    fn discarded_value() {
+        let findings = analyze(&parser::parse("test", "{ add(1, 2); 3 }").unwrap());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, AnalysisKind::DiscardedValue);
+        assert_eq!(findings[0].message, "result of calling 'add' is never used");
+        assert_eq!(
+            kinds("lambda(x) { x; [1, mul(x, 2)]; x }"),
+            vec![AnalysisKind::DiscardedValue, AnalysisKind::DiscardedValue]
+        );
+        // Side effects are kept, as is the last value and a builtin the program rebinds
+        assert_eq!(kinds("{ print(1); println(add(1, 2)); 3 }"), vec![]);
+        assert_eq!(
+            kinds("{ def add = lambda(a) { print(a) }; add(1); 3 }"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let source = "{ let y = 1; def f = lambda(n) { add(1, f(n)) }; add(n, 2); f(z) }";
+        let findings = analyze(&parser::parse_with_spans("test", source).unwrap());
+        let spans: Vec<(AnalysisKind, &str)> = findings
+            .iter()
+            .map(|a| {
+                let (offset, length) = a.span.expect("Finding should have a span");
+                (a.kind, &source[offset..offset + length])
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    AnalysisKind::NonTailRecursion,
+                    "def f = lambda(n) { add(1, f(n)) }"
+                ),
+                (AnalysisKind::DiscardedValue, "add(n, 2)"),
+                (AnalysisKind::UndefinedIdentifier, "n"),
+                (AnalysisKind::UndefinedIdentifier, "z"),
+                (AnalysisKind::UnusedBinding, "let y = 1"),
+            ]
+        );
+        // Without spans in the JSON AST there are none to report
+        let findings = analyze(&parser::parse("test", source).unwrap());
+        assert!(findings.iter().all(|a| a.span.is_none()));
+    }
+
+    #[test]
+    fn invalid_ast() {
+        let ast = serde_json::json!({ "Unknown": [] });
+        assert_eq!(analyze(&ast)[0].kind, AnalysisKind::InvalidAst);
+    }
+}
@@ -0,0 +1,671 @@
+use std::{fmt, rc::Rc};
+
+use serde_json::{Map, Value};
+
+use crate::{error::InterpError, interpreter::Expr};
+
+/// Internal representation of a program, compiled once from the JSON AST
+/// Evaluating this avoids walking the serde_json values again, such as on every call of a function
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ast {
+    // Integer literal
+    Integer(i64),
+    // Float literal, from JSON numbers with a fraction or exponent
+    Float(f64),
+    // Boolean literal
+    Boolean(bool),
+    // String literal
+    String(String),
+    // Character literal
+    Char(char),
+    // JSON array, each element is evaluated into a list
+    List(Vec<Ast>),
+    // Identifier to look up in the environment
+    Identifier(String),
+    // Expressions evaluated in a new local environment
+    Block(Vec<Ast>),
+    // Anonymous function with its parameter names, block, and doc string
+    // Shared with every function created from it
+    Lambda {
+        params: Rc<Vec<String>>,
+        // Annotated type of each parameter, empty if none are annotated
+        types: Rc<Vec<Option<ValueType>>>,
+        block: Rc<Vec<Ast>>,
+        doc: Option<Rc<str>>,
+    },
+    // Function followed by its arguments
+    Application(Vec<Ast>),
+    // Clauses of (condition, expression)
+    Cond(Vec<(Ast, Ast)>),
+    // Value compared to the first expression of each case, with the expression used when no case is equal
+    Switch {
+        value: Box<Ast>,
+        cases: Vec<(Ast, Ast)>,
+        default: Option<Box<Ast>>,
+    },
+    // Binds a name in a new local environment
+    // A block as the value runs in its own scope, only its last value is bound
+    Let {
+        name: String,
+        value: Box<Ast>,
+        block: Option<Vec<Ast>>,
+    },
+    // Binds each name to the element at the same position of a list value in a new local environment
+    Destructure {
+        names: Vec<String>,
+        value: Box<Ast>,
+    },
+    // Binds a name in a new local environment only for the body
+    LetIn {
+        name: String,
+        value: Box<Ast>,
+        body: Box<Ast>,
+    },
+    // Binds a name in the current local environment
+    Def {
+        name: String,
+        value: Box<Ast>,
+        block: Option<Vec<Ast>>,
+    },
+    // Binds a name in the current local environment which cannot be assigned to
+    Const {
+        name: String,
+        value: Box<Ast>,
+    },
+    // Evaluates the block, and if it errors evaluates the handler with the error bound to name
+    Try {
+        body: Vec<Ast>,
+        name: String,
+        handler: Vec<Ast>,
+    },
+    // Changes an existing binding
+    Assignment {
+        name: String,
+        value: Box<Ast>,
+    },
+    // Already evaluated value, JSON null is nil and quoted ASTs are data, also used for functions created by builtins
+    Value(Expr),
+}
+
+/// Most arrays and objects a JSON AST can be nested in, at least Interpreter::MAX_CALL_DEPTH
+/// Compiling and evaluating recurse on the nesting, so deeper ASTs are rejected before they can overflow the stack
+pub const MAX_NESTING: usize = 512;
+
+impl Ast {
+    /// Compiles a JSON AST produced by a parser
+    /// An AST nested deeper than MAX_NESTING is a stack overflow error, rather than crashing
+    pub fn from_value(val: &Value) -> Result<Ast, InterpError> {
+        check_nesting(val)?;
+        Ast::compile(val)
+    }
+
+    fn compile(val: &Value) -> Result<Ast, InterpError> {
+        match val {
+            // Numbers with a fraction or exponent are floats, even whole ones too large for i64 (1e20)
+            // Integers too large for i64 are rejected instead of losing precision as a float
+            Value::Number(num) if num.is_f64() => Ok(Ast::Float(num.as_f64().unwrap_or(f64::NAN))),
+            Value::Number(num) => {
+                num.as_i64()
+                    .map(Ast::Integer)
+                    .ok_or_else(|| InterpError::TypeError {
+                        expected: "i64".to_string(),
+                        found: num.to_string(),
+                    })
+            }
+            Value::Bool(bool) => Ok(Ast::Boolean(*bool)),
+            Value::Null => Ok(Ast::Value(Expr::Nil)),
+            Value::String(string) => Ok(Ast::String(string.to_string())),
+            Value::Array(arr) => Ok(Ast::List(compile_all(arr)?)),
+            Value::Object(obj) => compile_object(obj),
+        }
+    }
+}
+
+/// Type a function parameter can be annotated with, checked when the function is called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Integer,
+    Float,
+    // Integer or float
+    Number,
+    Boolean,
+    String,
+    Char,
+    List,
+    Pair,
+    Function,
+    Nil,
+}
+
+impl ValueType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "integer" => Some(ValueType::Integer),
+            "float" => Some(ValueType::Float),
+            "number" => Some(ValueType::Number),
+            "boolean" => Some(ValueType::Boolean),
+            "string" => Some(ValueType::String),
+            "char" => Some(ValueType::Char),
+            "list" => Some(ValueType::List),
+            "pair" => Some(ValueType::Pair),
+            "function" => Some(ValueType::Function),
+            "nil" => Some(ValueType::Nil),
+            _ => None,
+        }
+    }
+
+    /// Whether a value has this type
+    pub fn matches(&self, expr: &Expr) -> bool {
+        matches!(
+            (self, expr),
+            (ValueType::Integer, Expr::Integer(_))
+                | (ValueType::Float, Expr::Float(_))
+                | (ValueType::Number, Expr::Integer(_) | Expr::Float(_))
+                | (ValueType::Boolean, Expr::Boolean(_))
+                | (ValueType::String, Expr::String(_))
+                | (ValueType::Char, Expr::Char(_))
+                | (ValueType::List, Expr::List(_))
+                | (ValueType::Pair, Expr::Pair(_))
+                | (ValueType::Function, Expr::Function(_))
+                | (ValueType::Nil, Expr::Nil)
+        )
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Integer => "integer",
+            ValueType::Float => "float",
+            ValueType::Number => "number",
+            ValueType::Boolean => "boolean",
+            ValueType::String => "string",
+            ValueType::Char => "char",
+            ValueType::List => "list",
+            ValueType::Pair => "pair",
+            ValueType::Function => "function",
+            ValueType::Nil => "nil",
+        };
+        f.write_str(name)
+    }
+}
+
+fn compile_all(vals: &[Value]) -> Result<Vec<Ast>, InterpError> {
+    vals.iter().map(Ast::compile).collect()
+}
+
+/// Compile a JSON object, looking for the keys that correspond to certain behaviors
+/// Other keys are ignored, such as the "span" ([offset, length] in the source) a parser can add to each object
+fn compile_object(obj: &Map<String, Value>) -> Result<Ast, InterpError> {
+    // First see if there is an identifier
+    // true and false are always booleans, even if a JSON AST binds those names
+    match obj.get("Identifier").and_then(|val| val.as_str()) {
+        Some("true") => return Ok(Ast::Boolean(true)),
+        Some("false") => return Ok(Ast::Boolean(false)),
+        Some(binding) => return Ok(Ast::Identifier(binding.to_string())),
+        None => {}
+    }
+
+    if let Some(block) = obj.get("Block") {
+        Ok(Ast::Block(compile_block(block)?))
+    } else if let Some(c) = obj.get("Char") {
+        compile_char(c)
+    } else if let Some(lambda) = obj.get("Lambda") {
+        compile_lambda(lambda)
+    } else if let Some(arr) = obj.get("Application") {
+        match arr.as_array() {
+            Some(arr) if !arr.is_empty() => Ok(Ast::Application(compile_all(arr)?)),
+            Some(_) => Err(InterpError::ParseError {
+                message: "Function application on nothing.".to_string(),
+            }),
+            None => Err(InterpError::ParseError {
+                message: "Expected function and arguments.".to_string(),
+            }),
+        }
+    } else if let Some(arr) = obj.get("Cond") {
+        compile_cond(arr)
+    } else if let Some(arr) = obj.get("Switch") {
+        compile_switch(arr)
+    } else if let Some(arr) = obj.get("Let") {
+        let (name, value, block) = compile_var(arr, "let")?;
+        Ok(Ast::Let { name, value, block })
+    } else if let Some(arr) = obj.get("Destructure") {
+        compile_destructure(arr)
+    } else if let Some(arr) = obj.get("LetIn") {
+        compile_let_in(arr)
+    } else if let Some(arr) = obj.get("Def") {
+        let (name, value, block) = compile_var(arr, "def")?;
+        Ok(Ast::Def { name, value, block })
+    } else if let Some(arr) = obj.get("Const") {
+        let (name, value, _block) = compile_var(arr, "const")?;
+        Ok(Ast::Const { name, value })
+    } else if let Some(arr) = obj.get("Try") {
+        compile_try(arr)
+    } else if let Some(ast) = obj.get("Quote") {
+        Ok(Ast::Value(quoted(ast)))
+    } else if let Some(arr) = obj.get("Assignment") {
+        let (name, value, _block) = compile_var(arr, "assignment")?;
+        Ok(Ast::Assignment { name, value })
+    } else {
+        Err(InterpError::ParseError {
+            message: format!(
+                "Found JSON Object in AST but it does not contain a known keyword: {:?}",
+                obj
+            ),
+        })
+    }
+}
+
+/// Checks the nesting of a JSON AST with a stack rather than recursion, since it may be too deep to recurse on
+fn check_nesting(val: &Value) -> Result<(), InterpError> {
+    let mut pending = vec![(val, 1)];
+    while let Some((val, depth)) = pending.pop() {
+        let children: Vec<&Value> = match val {
+            Value::Array(arr) => arr.iter().collect(),
+            Value::Object(obj) => obj.values().collect(),
+            _ => continue,
+        };
+        if depth > MAX_NESTING {
+            return Err(InterpError::StackOverflow { limit: MAX_NESTING });
+        }
+        pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+    }
+    Ok(())
+}
+
+/// Compiles a character literal, a string of exactly one character
+fn compile_char(val: &Value) -> Result<Ast, InterpError> {
+    let mut chars = val.as_str().map(str::chars);
+    match chars.as_mut().map(|chars| (chars.next(), chars.next())) {
+        Some((Some(c), None)) => Ok(Ast::Char(c)),
+        _ => Err(InterpError::ParseError {
+            message: format!("{val} in char should be a string of one character."),
+        }),
+    }
+}
+
+/// Converts a JSON AST into data without evaluating it
+/// Objects become lists of their keys followed by values, so '{"Identifier": "x"}' is the list '[Identifier, x]'
+/// Spans are left out, so quoting gives the same data whether or not the parser added them
+pub(crate) fn quoted(val: &Value) -> Expr {
+    match val {
+        Value::Null => Expr::Nil,
+        Value::Bool(bool) => Expr::Boolean(*bool),
+        Value::Number(num) => match num.as_i64() {
+            Some(int) => Expr::Integer(int),
+            None => Expr::Float(num.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(string) => Expr::String(string.to_string()),
+        Value::Array(arr) => Expr::List(Rc::new(arr.iter().map(quoted).collect())),
+        Value::Object(obj) => Expr::List(Rc::new(
+            obj.iter()
+                .filter(|(key, _)| *key != "span")
+                .flat_map(|(key, val)| [Expr::String(key.to_string()), quoted(val)])
+                .collect(),
+        )),
+    }
+}
+
+/// Compiles the list of expressions inside of a "Block"
+fn compile_block(val: &Value) -> Result<Vec<Ast>, InterpError> {
+    match val.as_array() {
+        Some(arr) => compile_all(arr),
+        None => Err(InterpError::ParseError {
+            message: format!("{val} in block should be JSON array."),
+        }),
+    }
+}
+
+/// Compiles the "Lambda" object's value, which should have two list items, a parameters object and block object
+fn compile_lambda(val: &Value) -> Result<Ast, InterpError> {
+    let [parameters, block] = match val.as_array() {
+        Some(arr) if arr.len() == 2 => [&arr[0], &arr[1]],
+        _ => {
+            return Err(InterpError::ParseError {
+                message: "Function should have an associated parameters list and block."
+                    .to_string(),
+            })
+        }
+    };
+
+    let (params, types): (Vec<String>, Vec<Option<ValueType>>) = match parameters
+        .as_object()
+        .and_then(|obj| obj.get("Parameters"))
+        .and_then(|list| list.as_array())
+    {
+        Some(list) => list
+            .iter()
+            .map(compile_parameter)
+            .collect::<Result<Vec<_>, InterpError>>()?
+            .into_iter()
+            .unzip(),
+        _ => {
+            return Err(InterpError::ParseError {
+                message: "Parameters list is missing.".to_string(),
+            })
+        }
+    };
+
+    let block = block
+        .as_object()
+        .and_then(|obj| obj.get("Block"))
+        .ok_or_else(|| InterpError::ParseError {
+            message: "Function should contain a block.".to_string(),
+        })?;
+
+    // A string literal starting a block of more expressions is the doc string, it would have no effect when evaluated
+    let mut block = compile_block(block)?;
+    let doc = match block.as_slice() {
+        [Ast::String(doc), _, ..] => Some(Rc::from(doc.as_str())),
+        _ => None,
+    };
+    if doc.is_some() {
+        block.remove(0);
+    }
+
+    // Calls of functions without annotations skip checking entirely
+    let types = match types.iter().any(Option::is_some) {
+        true => types,
+        false => vec![],
+    };
+
+    Ok(Ast::Lambda {
+        params: Rc::new(params),
+        types: Rc::new(types),
+        block: Rc::new(block),
+        doc,
+    })
+}
+
+/// Compiles a parameter object, an identifier with an optional "Type" name
+fn compile_parameter(param: &Value) -> Result<(String, Option<ValueType>), InterpError> {
+    let name = param
+        .as_object()
+        .and_then(|obj| obj.get("Identifier"))
+        .and_then(|i| i.as_str())
+        .ok_or_else(|| InterpError::ParseError {
+            message: "All parameters must be an identifier.".to_string(),
+        })?;
+
+    let value_type = match param.get("Type") {
+        None => None,
+        Some(type_name) => {
+            let value_type = type_name.as_str().and_then(ValueType::from_name);
+            Some(value_type.ok_or_else(|| InterpError::ParseError {
+                message: format!(
+                    "Unknown type {} for parameter '{}', expected one of integer, float, number, boolean, string, char, list, pair, function, or nil.",
+                    type_name, name
+                ),
+            })?)
+        }
+    };
+
+    Ok((name.to_string(), value_type))
+}
+
+/// Compiles the "Cond" object's value, a list of "Clause" objects
+fn compile_cond(val: &Value) -> Result<Ast, InterpError> {
+    Ok(Ast::Cond(compile_clauses(val, "cond")?))
+}
+
+/// Compiles the "Switch" object's value, the value, a list of "Clause" objects, and an optional default
+fn compile_switch(val: &Value) -> Result<Ast, InterpError> {
+    let (value, clauses, default) = match val.as_array().map(|arr| arr.as_slice()) {
+        Some([value, clauses]) => (value, clauses, None),
+        Some([value, clauses, default]) => (value, clauses, Some(default)),
+        _ => {
+            return Err(InterpError::ParseError {
+                message: format!("{val} in switch expression should be a JSON array of a value, clauses, and an optional default."),
+            })
+        }
+    };
+
+    Ok(Ast::Switch {
+        value: Box::new(Ast::compile(value)?),
+        cases: compile_clauses(clauses, "switch")?,
+        default: default
+            .map(|default| Ast::compile(default).map(Box::new))
+            .transpose()?,
+    })
+}
+
+/// Compiles a list of "Clause" objects into pairs of their two expressions
+fn compile_clauses(val: &Value, expression_type: &str) -> Result<Vec<(Ast, Ast)>, InterpError> {
+    let Value::Array(arr) = val else {
+        return Err(InterpError::ParseError {
+            message: format!("{val} in {expression_type} expression should be JSON array."),
+        });
+    };
+
+    arr.iter()
+        .map(|statement| {
+            // Splits the condition and expression away
+            match statement
+                .get("Clause")
+                .and_then(|clause| clause.as_array())
+                .map(|clause| clause.as_slice())
+            {
+                Some([condition, expr]) => Ok((Ast::compile(condition)?, Ast::compile(expr)?)),
+                _ => Err(InterpError::ParseError {
+                    message: "Clause did not contain both a condition and expression.".to_string(),
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Compiles the "Destructure" object's value, a list of identifiers and the value
+fn compile_destructure(val: &Value) -> Result<Ast, InterpError> {
+    let Some([Value::Array(idents), value]) = val.as_array().map(|arr| arr.as_slice()) else {
+        return Err(InterpError::ParseError {
+            message: format!(
+                "{val} in destructuring let should be a JSON array of identifiers and a value."
+            ),
+        });
+    };
+    let names = idents
+        .iter()
+        .map(|ident| {
+            ident
+                .get("Identifier")
+                .and_then(|n| n.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| InterpError::ParseError {
+                    message: "Expecting only identifiers in destructuring let".to_string(),
+                })
+        })
+        .collect::<Result<Vec<String>, InterpError>>()?;
+
+    Ok(Ast::Destructure {
+        names,
+        value: Box::new(Ast::compile(value)?),
+    })
+}
+
+/// Compiles the "LetIn" object's value, the identifier, value, and body
+fn compile_let_in(val: &Value) -> Result<Ast, InterpError> {
+    let Some([ident, value, body]) = val.as_array().map(|arr| arr.as_slice()) else {
+        return Err(InterpError::ParseError {
+            message: format!("{val} in let-in expression should be a JSON array of an identifier, value, and body."),
+        });
+    };
+    let name = ident
+        .get("Identifier")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| InterpError::ParseError {
+            message: "Expecting an identifier in let-in expression".to_string(),
+        })?;
+
+    Ok(Ast::LetIn {
+        name: name.to_string(),
+        value: Box::new(Ast::compile(value)?),
+        body: Box::new(Ast::compile(body)?),
+    })
+}
+
+/// Compiles the "Try" object's value, the body block, the identifier of the error, and the handler block
+fn compile_try(val: &Value) -> Result<Ast, InterpError> {
+    let Some([body, name, handler]) = val.as_array().map(|arr| arr.as_slice()) else {
+        return Err(InterpError::ParseError {
+            message: format!(
+                "{val} in try expression should be a JSON array of a block, identifier, and block."
+            ),
+        });
+    };
+    let block = |val: &Value| {
+        val.get("Block").map(compile_block).unwrap_or_else(|| {
+            Err(InterpError::ParseError {
+                message: "Try expression should contain a block and a handler block.".to_string(),
+            })
+        })
+    };
+    let name = name
+        .get("Identifier")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| InterpError::ParseError {
+            message: "Expecting an identifier for the error in try expression".to_string(),
+        })?;
+
+    Ok(Ast::Try {
+        body: block(body)?,
+        name: name.to_string(),
+        handler: block(handler)?,
+    })
+}
+
+/// Name, value, and optional block of a variable expression
+type Var = (String, Box<Ast>, Option<Vec<Ast>>);
+
+/// Compiles creating a new variable
+fn compile_var(val: &Value, expression_type: &str) -> Result<Var, InterpError> {
+    match val.as_array().map(|arr| arr.as_slice()) {
+        Some([ident, value, rest @ ..]) => {
+            let ident_name = ident
+                .get("Identifier")
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| InterpError::ParseError {
+                    message: format!("Expecting an identifier in {expression_type} expression"),
+                })?;
+
+            // Expect third to be a block if exists (custom parser does not include block after lets)
+            let block = rest
+                .first()
+                .and_then(|v| v.as_object().and_then(|o| o.get("Block")))
+                .map(compile_block)
+                .transpose()?;
+
+            Ok((
+                ident_name.to_string(),
+                Box::new(Ast::compile(value)?),
+                block,
+            ))
+        }
+        _ => Err(InterpError::ParseError {
+            message: format!("{val} in variable expression should be JSON array."),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_lambda_block() -> Result<(), InterpError> {
+        let val = serde_json::from_str(
+            r#"{"Lambda": [
+                {"Parameters": [{"Identifier": "n"}]},
+                {"Block": [{"Application": [{"Identifier": "add"}, {"Identifier": "n"}, 1]}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ast::Lambda {
+                params: Rc::new(vec!["n".to_string()]),
+                types: Rc::new(vec![]),
+                block: Rc::new(vec![Ast::Application(vec![
+                    Ast::Identifier("add".to_string()),
+                    Ast::Identifier("n".to_string()),
+                    Ast::Integer(1),
+                ])]),
+                doc: None,
+            },
+            Ast::from_value(&val)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn compile_lambda_doc() -> Result<(), InterpError> {
+        let val = serde_json::from_str(
+            r#"{"Lambda": [{"Parameters": []}, {"Block": ["Returns one", 1]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Ast::Lambda {
+                params: Rc::new(vec![]),
+                types: Rc::new(vec![]),
+                block: Rc::new(vec![Ast::Integer(1)]),
+                doc: Some(Rc::from("Returns one")),
+            },
+            Ast::from_value(&val)?
+        );
+
+        // A string that is the only expression is the result, not a doc string
+        let val = serde_json::from_str(r#"{"Lambda": [{"Parameters": []}, {"Block": ["one"]}]}"#)
+            .unwrap();
+        assert!(matches!(
+            Ast::from_value(&val)?,
+            Ast::Lambda { doc: None, .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_spans() -> Result<(), InterpError> {
+        let source = r#"{
+            def f = lambda(n: integer) { "Doc"; cond (zero?(n) => 1) (true => n) };
+            let x = quote(add(1, 2)) in [x, 5 |> f]
+        }"#;
+        let plain = parser::parse("test", source).unwrap();
+        let spanned = parser::parse_with_spans("test", source).unwrap();
+        assert_ne!(plain, spanned);
+        assert_eq!(Ast::from_value(&plain)?, Ast::from_value(&spanned)?);
+        assert_eq!(quoted(&plain), quoted(&spanned));
+
+        Ok(())
+    }
+
+    #[test]
+    fn compile_large_numbers() -> Result<(), InterpError> {
+        // Whole floats outside of the i64 range are still floats
+        let val = serde_json::from_str("[1e20, 100000000000000000000.0, -1E19]").unwrap();
+        let Ast::List(floats) = Ast::from_value(&val)? else {
+            panic!("Expected a list");
+        };
+        assert!(
+            matches!(floats[..], [Ast::Float(a), Ast::Float(b), Ast::Float(c)]
+            if a == 1e20 && b == 1e20 && c == -1e19)
+        );
+        assert!(matches!(Ast::from_value(&serde_json::json!(1e20))?, Ast::Float(f) if f == 1e20));
+
+        // Integers outside of it are not rounded to a float
+        for source in ["100000000000000000000", "-9223372036854775809"] {
+            let val = serde_json::from_str(source).unwrap();
+            assert!(
+                Ast::from_value(&val).is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compile_unknown_object() {
+        let val = serde_json::from_str(r#"{"Unknown": []}"#).unwrap();
+        assert!(Ast::from_value(&val).is_err_and(|e| matches!(e, InterpError::ParseError { .. })));
+    }
+}
@@ -1,78 +1,738 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
 use crate::error::InterpError;
-use crate::functions::Function::CoreFunction;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::functions::clock;
+#[cfg(feature = "parser")]
+use crate::functions::eval;
+use crate::functions::Function::{self, CoreFunction};
 use crate::functions::{
-    add, as_list, concat, contains, dbg, div, eq, get, greater, length, less, mul, print, println,
-    rem, set, sort, sub, to_lowercase, to_uppercase, zero,
+    add, all, and, any, append, arity, as_list, assert, assert_equal, bitand, bitor, bitxor, ceil,
+    char_at, char_code, char_from_code, compare_values, compose, concat, cons, constantly,
+    contains, copy, count, dbg, div, doc, drop, enumerate, env_names, eq, err, error, escape, find,
+    floor, format, from_json, gcd, get, greater, head, identity, inspect, is_ok, lcm, length, less,
+    list_to_pairs, list_to_string, modulo, mul, not, ok, or, pad_left, pad_right, pairs_to_list,
+    print, println, product, random, rem, round, set, shl, shr, sort, split_lines, string_replace,
+    string_to_list, sub, sum, tail, take, to_float, to_int, to_json, to_lowercase, to_uppercase,
+    unique, unwrap, zero,
 };
-use crate::interpreter::Expr;
+use crate::interpreter::{Expr, Interpreter};
+use crate::random::Rng;
+use Arity::{AtLeast, Between, Exact};
 
 /// Environment of running interpreter
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq)]
 pub struct LocalEnvironment {
     // Stack of environments, deepest is default, next is global, then local, etc.
     variables: HashMap<String, Expr>,
+    // Names in variables bound by const, which cannot be assigned to
+    constants: HashSet<String>,
     parent: Option<Rc<RefCell<LocalEnvironment>>>,
+    // Number of parents, the default environment has none
+    depth: usize,
+    // Where names not in variables were found in the parents, only used once this environment has children
+    cache: LookupCache,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+thread_local! {
+    // Bumped whenever a new name is bound in an environment with children, which could shadow a cached name
+    static CACHE_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+// Walking a shorter chain of parents is faster than checking the cache
+const CACHE_MIN_DEPTH: usize = 4;
+
+/// Epoch the entry was cached in, and the environment holding the name
+type CacheEntry = (u64, Rc<RefCell<LocalEnvironment>>);
+
+/// Bindings of one environment at the time they were saved, restore puts them back in that same environment
+#[derive(Debug, Clone)]
+pub(crate) struct SavedBindings {
+    env: Rc<RefCell<LocalEnvironment>>,
+    variables: HashMap<String, Expr>,
+    constants: HashSet<String>,
+}
+
+impl SavedBindings {
+    /// Replaces the environment's bindings with the saved ones, undoing any made or assigned since
+    pub(crate) fn restore(self) {
+        let mut env = self.env.borrow_mut();
+        env.variables = self.variables;
+        env.constants = self.constants;
+        // Names bound since being saved are removed, so cached holders of them could now be wrong
+        CACHE_EPOCH.with(|epoch| epoch.set(epoch.get() + 1));
+    }
+}
+
+/// Cache of which parent environment holds a name, shared by all lookups from child environments
+/// Entries from an older epoch are stale, assignment does not move a binding so it does not invalidate them
+#[derive(Default, Clone)]
+struct LookupCache {
+    entries: RefCell<HashMap<String, CacheEntry>>,
+    // Set once a child environment is created, environments without children are not worth caching in
+    has_children: Cell<bool>,
+}
+
+// The cache never changes the result of a lookup, so it is left out of comparisons and debug output
+impl PartialEq for LookupCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for LookupCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LookupCache")
+    }
+}
+
+// Shows only this environment's own names, the parents are shared by many environments and can be long chains
+impl fmt::Debug for LocalEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = self.variables.keys().collect::<Vec<&String>>();
+        names.sort();
+        let mut constants = self.constants.iter().collect::<Vec<&String>>();
+        constants.sort();
+        f.debug_struct("LocalEnvironment")
+            .field("names", &names)
+            .field("constants", &constants)
+            .field("depth", &self.depth)
+            .field("has_parent", &self.parent.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
     // Flag for whether to enable lexical scope or not (default true)
     pub lexical_scope: bool,
+    // Flag for whether an unbound identifier evaluates to its name as a string instead of erroring (default false)
+    pub undefined_as_symbol: bool,
     // Flag for whether to store output instead of directly outputing it
     pub store_output: bool,
     // All output stored, able to be used for environments that do not support printing normally (WASM)
     pub output: Vec<String>,
+    // Most output (in bytes) to store, output after it is dropped
+    pub output_limit: Option<usize>,
+    // Total length of the stored output
+    pub output_len: usize,
+    // Random number generator used by the random builtin
+    pub rng: Rng,
+    // How many calls of the eval builtin are running inside of each other
+    pub eval_depth: usize,
+    // When the interpreter was created, used by the clock builtin (Instant is not supported on WASM)
+    #[cfg(not(target_arch = "wasm32"))]
+    pub start: Instant,
+    // Builtins available when the interpreter was created, kept even if the program shadows them
+    pub builtins: Vec<BuiltinInfo>,
+    // Called with each piece of output as it is stored, so a host can show it before the program ends
+    pub output_hook: Option<OutputHook>,
+}
+
+/// Function receiving each piece of stored output
+type OutputFn = dyn FnMut(&str);
+
+/// Callback receiving stored output, clones of an environment share the same callback
+#[derive(Clone)]
+pub struct OutputHook(Rc<RefCell<OutputFn>>);
+
+impl OutputHook {
+    pub fn new(hook: impl FnMut(&str) + 'static) -> Self {
+        Self(Rc::new(RefCell::new(hook)))
+    }
+
+    fn call(&self, output: &str) {
+        (self.0.borrow_mut())(output)
+    }
+}
+
+// The hook only observes output, so like the lookup cache it is left out of comparisons and debug output
+impl PartialEq for OutputHook {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for OutputHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputHook")
+    }
+}
+
+/// Groups of builtins to register beyond the core arithmetic, comparison, and list functions
+/// Lets embedders running untrusted programs leave out builtins with side effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinSet {
+    // print, println, and dbg
+    pub output: bool,
+    // Functions on strings and characters
+    pub strings: bool,
+    // random
+    pub random: bool,
+    // clock, never available on WASM
+    pub clock: bool,
+    // env_names
+    pub environment: bool,
+}
+
+impl BuiltinSet {
+    /// Only the core builtins
+    pub fn minimal() -> Self {
+        Self {
+            output: false,
+            strings: false,
+            random: false,
+            clock: false,
+            environment: false,
+        }
+    }
+
+    /// Every builtin
+    pub fn full() -> Self {
+        Self {
+            output: true,
+            strings: true,
+            random: true,
+            clock: true,
+            environment: true,
+        }
+    }
+}
+
+/// Number of arguments a builtin function accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    // Inclusive range of argument counts
+    Between(usize, usize),
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "{}+", n),
+            Arity::Between(min, max) => write!(f, "{}-{}", min, max),
+        }
+    }
+}
+
+impl Arity {
+    /// Errors unless the builtin accepts the number of arguments it was called with
+    pub fn check(self, func: &str, got: usize) -> Result<(), InterpError> {
+        let accepted = match self {
+            Arity::Exact(n) => got == n,
+            Arity::AtLeast(min) => got >= min,
+            Arity::Between(min, max) => (min..=max).contains(&got),
+        };
+        if accepted {
+            return Ok(());
+        }
+        Err(InterpError::ArgumentError {
+            func: func.to_string(),
+            expected: self,
+            got,
+        })
+    }
+}
+
+/// Description of a builtin function, for tooling such as autocomplete and documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuiltinInfo {
+    pub name: String,
+    pub arity: Arity,
+    pub doc: &'static str,
 }
 
 impl LocalEnvironment {
     /// Default environment of the interpreter with all builtins
     pub fn default_environment() -> Self {
+        Self::with_builtins(BuiltinSet::full())
+    }
+
+    /// Root environment with the core builtins and the selected groups of builtins
+    pub fn with_builtins(builtins: BuiltinSet) -> Self {
         let mut env = Self {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             parent: None,
+            depth: 0,
+            cache: LookupCache::default(),
         };
 
-        env.add_builtin_func("print", print);
-        env.add_builtin_func("println", println);
-        env.add_builtin_func("dbg", dbg);
-        env.add_builtin_func("equal?", eq);
-        env.add_builtin_func("greater?", greater);
-        env.add_builtin_func("less?", less);
-        env.add_builtin_func("add", add);
-        env.add_builtin_func("sub", sub);
-        env.add_builtin_func("mul", mul);
-        env.add_builtin_func("div", div);
-        env.add_builtin_func("rem", rem);
-        env.add_builtin_func("zero?", zero);
-        env.add_builtin_func("to_uppercase", to_uppercase);
-        env.add_builtin_func("to_lowercase", to_lowercase);
-        env.add_builtin_func("concat", concat);
-        env.add_builtin_func("contains", contains);
-        env.add_builtin_func("length", length);
-        env.add_builtin_func("as_list", as_list);
-        env.add_builtin_func("get", get);
-        env.add_builtin_func("set", set);
-        env.add_builtin_func("sort", sort);
+        if builtins.output {
+            env.add_builtin_func(
+                "print",
+                AtLeast(0),
+                "Prints the arguments without a newline",
+                print,
+            );
+            env.add_builtin_func(
+                "println",
+                AtLeast(0),
+                "Prints the arguments separated by spaces, then a newline",
+                println,
+            );
+            env.add_builtin_func(
+                "dbg",
+                AtLeast(0),
+                "Prints the debug form of each argument on its own line",
+                dbg,
+            );
+            env.add_builtin_func(
+                "inspect",
+                Exact(1),
+                "Prints the argument followed by a newline, then returns it",
+                inspect,
+            );
+        }
+        if builtins.strings {
+            env.add_builtin_func(
+                "escape",
+                Exact(1),
+                "String of the argument with control characters shown as escapes",
+                escape,
+            );
+            env.add_builtin_func(
+                "to_uppercase",
+                AtLeast(1),
+                "Uppercase copy of a string, or a list of them for several strings",
+                to_uppercase,
+            );
+            env.add_builtin_func(
+                "to_lowercase",
+                AtLeast(1),
+                "Lowercase copy of a string, or a list of them for several strings",
+                to_lowercase,
+            );
+            env.add_builtin_func("concat", AtLeast(0), "Joins strings together", concat);
+            env.add_builtin_func(
+                "to_json",
+                Exact(1),
+                "JSON string of a value, nil is null and lists are arrays",
+                to_json,
+            );
+            env.add_builtin_func(
+                "from_json",
+                Exact(1),
+                "Value of a JSON string, null is nil and arrays are lists (objects are not supported)",
+                from_json,
+            );
+            env.add_builtin_func(
+                "format",
+                AtLeast(1),
+                "Substitutes the arguments for the {} placeholders of a template string",
+                format,
+            );
+            env.add_builtin_func(
+                "contains",
+                AtLeast(2),
+                "Whether every string after the first contains the first",
+                contains,
+            );
+            env.add_builtin_func(
+                "string_replace",
+                Between(3, 4),
+                "Replaces a pattern in a string, only the first if the fourth argument is true",
+                string_replace,
+            );
+            env.add_builtin_func(
+                "char_code",
+                Exact(1),
+                "Unicode scalar value of a character, or a single character string",
+                char_code,
+            );
+            env.add_builtin_func(
+                "char_from_code",
+                Exact(1),
+                "Character of a Unicode scalar value",
+                char_from_code,
+            );
+            env.add_builtin_func(
+                "char_at",
+                Exact(2),
+                "Character at an index of a string, negative indices count back from the end",
+                char_at,
+            );
+            env.add_builtin_func(
+                "split_lines",
+                Between(1, 2),
+                "Lines of a string split on \\n or \\r\\n, keeping a trailing empty line if the second argument is true",
+                split_lines,
+            );
+            env.add_builtin_func(
+                "pad_left",
+                Exact(3),
+                "Pads the start of a string with a character up to a width",
+                pad_left,
+            );
+            env.add_builtin_func(
+                "pad_right",
+                Exact(3),
+                "Pads the end of a string with a character up to a width",
+                pad_right,
+            );
+            env.add_builtin_func(
+                "string_to_list",
+                Exact(1),
+                "List of the characters of a string, each as a string",
+                string_to_list,
+            );
+            env.add_builtin_func(
+                "list_to_string",
+                Exact(1),
+                "Concatenates a list of single characters into a string",
+                list_to_string,
+            );
+        }
+        if builtins.random {
+            env.add_builtin_func(
+                "random",
+                Exact(2),
+                "Random integer from the first argument up to (not including) the second",
+                random,
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if builtins.clock {
+            env.add_builtin_func(
+                "clock",
+                Exact(0),
+                "Milliseconds elapsed since the interpreter was created",
+                clock,
+            );
+        }
+        if builtins.environment {
+            env.add_builtin_func(
+                "env_names",
+                Exact(0),
+                "Names of every binding in scope, innermost first",
+                env_names,
+            );
+        }
+
+        env.add_builtin_func(
+            "equal?",
+            AtLeast(0),
+            "Whether all arguments are equal to the first",
+            eq,
+        );
+        env.add_builtin_func(
+            "assert",
+            Exact(1),
+            "Errors unless the argument is true",
+            assert,
+        );
+        env.add_builtin_func(
+            "assert_equal",
+            Exact(2),
+            "Errors unless both arguments are equal",
+            assert_equal,
+        );
+        env.add_builtin_func(
+            "error",
+            Exact(1),
+            "Raises an error with the message, which try can catch",
+            error,
+        );
+        env.add_builtin_func(
+            "greater?",
+            Exact(2),
+            "Whether the first argument is greater than the second",
+            greater,
+        );
+        env.add_builtin_func(
+            "less?",
+            Exact(2),
+            "Whether the first argument is less than the second",
+            less,
+        );
+        env.add_builtin_func(
+            "compare",
+            Exact(2),
+            "-1, 0, or 1 as the first argument is less than, equal to, or greater than the second",
+            compare_values,
+        );
+        env.add_builtin_func("add", AtLeast(0), "Sum of integers", add);
+        env.add_builtin_func(
+            "sub",
+            AtLeast(0),
+            "Subtracts the rest of the integers from the first",
+            sub,
+        );
+        env.add_builtin_func("mul", AtLeast(0), "Product of integers", mul);
+        env.add_builtin_func(
+            "div",
+            Exact(2),
+            "Integer division of the first argument by the second",
+            div,
+        );
+        env.add_builtin_func(
+            "rem",
+            Exact(2),
+            "Remainder of dividing the first argument by the second",
+            rem,
+        );
+        env.add_builtin_func(
+            "mod",
+            Exact(2),
+            "Euclidean modulo of the first argument by the second, never negative",
+            modulo,
+        );
+        env.add_builtin_func(
+            "gcd",
+            Exact(2),
+            "Greatest common divisor of two integers",
+            gcd,
+        );
+        env.add_builtin_func(
+            "lcm",
+            Exact(2),
+            "Least common multiple of two integers",
+            lcm,
+        );
+        env.add_builtin_func("bitand", Exact(2), "Bitwise and of two integers", bitand);
+        env.add_builtin_func("bitor", Exact(2), "Bitwise or of two integers", bitor);
+        env.add_builtin_func(
+            "bitxor",
+            Exact(2),
+            "Bitwise exclusive or of two integers",
+            bitxor,
+        );
+        env.add_builtin_func(
+            "shl",
+            Exact(2),
+            "Shifts an integer left by 0 to 63 bits",
+            shl,
+        );
+        env.add_builtin_func(
+            "shr",
+            Exact(2),
+            "Shifts an integer right by 0 to 63 bits, keeping its sign",
+            shr,
+        );
+        env.add_builtin_func(
+            "to_float",
+            Exact(1),
+            "Converts an integer to a float",
+            to_float,
+        );
+        env.add_builtin_func(
+            "to_int",
+            Exact(1),
+            "Converts a float to an integer, dropping the fraction",
+            to_int,
+        );
+        env.add_builtin_func(
+            "floor",
+            Exact(1),
+            "Largest integer less than or equal to a number",
+            floor,
+        );
+        env.add_builtin_func(
+            "ceil",
+            Exact(1),
+            "Smallest integer greater than or equal to a number",
+            ceil,
+        );
+        env.add_builtin_func(
+            "round",
+            Exact(1),
+            "Nearest integer to a number, halves round away from zero",
+            round,
+        );
+        env.add_builtin_func("zero?", Exact(1), "Whether an integer is zero", zero);
+        env.add_builtin_func("not", Exact(1), "Negation of a boolean", not);
+        env.add_builtin_func("and", AtLeast(0), "Whether every boolean is true", and);
+        env.add_builtin_func("or", AtLeast(0), "Whether any boolean is true", or);
+        env.add_builtin_func(
+            "length",
+            Exact(1),
+            "Length of a string, list, or linked list of pairs",
+            length,
+        );
+        env.add_builtin_func("as_list", AtLeast(0), "List of the arguments", as_list);
+        env.add_builtin_func(
+            "get",
+            Exact(2),
+            "Element at an index of a list or linked list of pairs",
+            get,
+        );
+        env.add_builtin_func(
+            "set",
+            Exact(3),
+            "Copy of a list with the element at an index replaced",
+            set,
+        );
+        env.add_builtin_func(
+            "append",
+            Exact(2),
+            "Copy of a list with an element added to the end",
+            append,
+        );
+        env.add_builtin_func("copy", Exact(1), "Deep copy of a value", copy);
+        env.add_builtin_func(
+            "count",
+            Exact(2),
+            "Number of elements of a list equal to the target",
+            count,
+        );
+        env.add_builtin_func("take", Exact(2), "First n elements of a list", take);
+        env.add_builtin_func("drop", Exact(2), "List without its first n elements", drop);
+        env.add_builtin_func(
+            "find",
+            Exact(2),
+            "First element of a list matching a predicate, or nil",
+            find,
+        );
+        env.add_builtin_func(
+            "any?",
+            Exact(2),
+            "Whether any element of a list matches a predicate",
+            any,
+        );
+        env.add_builtin_func(
+            "all?",
+            Exact(2),
+            "Whether every element of a list matches a predicate",
+            all,
+        );
+        env.add_builtin_func(
+            "sort",
+            Exact(1),
+            "Stable sorted copy of a list of numbers, strings, characters, or lists",
+            sort,
+        );
+        env.add_builtin_func(
+            "enumerate",
+            Exact(1),
+            "List of [index, element] pairs for each element of a list",
+            enumerate,
+        );
+        env.add_builtin_func(
+            "unique",
+            Exact(1),
+            "Copy of a list without duplicates, keeping the first of each",
+            unique,
+        );
+        env.add_builtin_func("sum", Exact(1), "Sum of a list of integers", sum);
+        env.add_builtin_func(
+            "product",
+            Exact(1),
+            "Product of a list of integers",
+            product,
+        );
+        env.add_builtin_func(
+            "cons",
+            Exact(2),
+            "Pair of an element in front of a linked list (a pair or nil), sharing the rest",
+            cons,
+        );
+        env.add_builtin_func("head", Exact(1), "First element of a pair or list", head);
+        env.add_builtin_func(
+            "tail",
+            Exact(1),
+            "Rest of a pair, or a copy of the rest of a list",
+            tail,
+        );
+        env.add_builtin_func(
+            "list_to_pairs",
+            Exact(1),
+            "Linked list of pairs with the elements of a list",
+            list_to_pairs,
+        );
+        env.add_builtin_func(
+            "pairs_to_list",
+            Exact(1),
+            "List of the elements of a linked list of pairs",
+            pairs_to_list,
+        );
+        env.add_builtin_func(
+            "arity",
+            Exact(1),
+            "Number of parameters of a user function, or nil for builtins",
+            arity,
+        );
+        env.add_builtin_func(
+            "doc",
+            Exact(1),
+            "Doc string of a function, from a string starting its block, or nil",
+            doc,
+        );
+        env.add_builtin_func(
+            "compose",
+            Exact(2),
+            "Function applying the second function and then the first",
+            compose,
+        );
+        env.add_builtin_func("identity", Exact(1), "Its argument unchanged", identity);
+        env.add_builtin_func(
+            "constantly",
+            Exact(1),
+            "Function of no arguments always returning the given value",
+            constantly,
+        );
+        env.add_builtin_func(
+            "ok",
+            Exact(1),
+            "Successful result holding a value, the list [\"Ok\", value]",
+            ok,
+        );
+        env.add_builtin_func(
+            "err",
+            Exact(1),
+            "Failed result holding an error, the list [\"Err\", error]",
+            err,
+        );
+        env.add_builtin_func("is_ok?", Exact(1), "Whether a result is successful", is_ok);
+        env.add_builtin_func(
+            "unwrap",
+            Exact(1),
+            "Value of a successful result, errors on a failed one",
+            unwrap,
+        );
+        #[cfg(feature = "parser")]
+        env.add_builtin_func(
+            "eval",
+            Exact(1),
+            "Parses and evaluates a string of source in the current scope",
+            eval,
+        );
         env.add_builtin("x", Expr::Integer(10));
         env.add_builtin("v", Expr::Integer(5));
         env.add_builtin("i", Expr::Integer(1));
-        env.add_builtin("true", Expr::Boolean(true));
-        env.add_builtin("false", Expr::Boolean(false));
+        // Ends a linked list built with cons
+        env.add_builtin("nil", Expr::Nil);
 
         env
     }
 
     pub fn from_parent(parent: Rc<RefCell<Self>>) -> Rc<RefCell<LocalEnvironment>> {
+        let depth = {
+            let parent = parent.borrow();
+            parent.cache.has_children.set(true);
+            parent.depth + 1
+        };
         Rc::new(RefCell::new(Self {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             parent: Some(parent),
+            depth,
+            cache: LookupCache::default(),
         }))
     }
 
+    /// Number of parents, 0 for the environment of builtins
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
     pub fn return_parent(&self) -> Option<Rc<RefCell<LocalEnvironment>>> {
         self.parent.clone()
     }
@@ -82,28 +742,65 @@ impl LocalEnvironment {
         self.variables.insert(name.to_string(), expr);
     }
 
-    /// Adds function to builtins (bottom of stack)
+    /// Adds function to builtins (bottom of stack), with the arity and doc string shown by tooling
     fn add_builtin_func(
         &mut self,
         name: &str,
-        func: fn(&[Expr], &mut Environment) -> Result<Expr, InterpError>,
+        arity: Arity,
+        doc: &'static str,
+        func: fn(&[Expr], &mut Interpreter) -> Result<Expr, InterpError>,
     ) {
         self.variables.insert(
             name.to_string(),
             Expr::Function(CoreFunction {
                 name: name.to_string(),
                 func,
+                arity,
+                doc,
             }),
         );
     }
 
+    /// Information about the builtin functions bound directly in this environment, sorted by name
+    pub fn builtin_info(&self) -> Vec<BuiltinInfo> {
+        let mut builtins: Vec<BuiltinInfo> = self
+            .variables
+            .iter()
+            .filter_map(|(name, expr)| match expr {
+                Expr::Function(CoreFunction { arity, doc, .. }) => Some(BuiltinInfo {
+                    name: name.clone(),
+                    arity: *arity,
+                    doc,
+                }),
+                _ => None,
+            })
+            .collect();
+        builtins.sort_by(|a, b| a.name.cmp(&b.name));
+        builtins
+    }
+
     /// Bind a group of bindings to expressions that are passed in as a tuple pair
     /// Adds to top environment of the stack
     pub fn bind(&mut self, pairs: Vec<(&String, &Expr)>) {
-        let local_env: &mut HashMap<String, Expr> = &mut self.variables;
         for (binding, expr) in pairs {
-            local_env.insert(binding.to_string(), expr.clone());
+            // A new binding of the same name replaces a constant
+            self.constants.remove(binding);
+            self.insert(binding, expr);
+        }
+    }
+
+    /// Bind a name in the top environment that cannot be changed by assignment
+    pub fn bind_const(&mut self, binding: &str, expr: &Expr) {
+        self.insert(binding, expr);
+        self.constants.insert(binding.to_string());
+    }
+
+    fn insert(&mut self, binding: &str, expr: &Expr) {
+        // A new name could shadow one that children have cached from further up the chain
+        if self.cache.has_children.get() && !self.variables.contains_key(binding) {
+            CACHE_EPOCH.with(|epoch| epoch.set(epoch.get() + 1));
         }
+        self.variables.insert(binding.to_string(), expr.clone());
     }
 
     /// Look for binding in local (top) environment first, then search deeper
@@ -112,36 +809,209 @@ impl LocalEnvironment {
             return Some(expr.clone());
         }
 
+        let holder = Self::holder(self.parent.as_ref()?, binding)?;
+        let expr = holder.borrow().variables.get(binding).cloned();
+        expr
+    }
+
+    /// Environment in the chain starting at env which binds the name, using and filling the lookup caches
+    fn holder(env: &Rc<RefCell<Self>>, binding: &str) -> Option<Rc<RefCell<Self>>> {
+        let this = env.borrow();
+        if this.variables.contains_key(binding) {
+            return Some(env.clone());
+        }
+
+        let use_cache = this.cache.has_children.get() && this.depth >= CACHE_MIN_DEPTH;
+        let epoch = CACHE_EPOCH.with(Cell::get);
+        if use_cache {
+            if let Some((cached_epoch, holder)) = this.cache.entries.borrow().get(binding) {
+                if *cached_epoch == epoch {
+                    return Some(holder.clone());
+                }
+            }
+        }
+
+        let holder = Self::holder(this.parent.as_ref()?, binding)?;
+        if use_cache {
+            this.cache
+                .entries
+                .borrow_mut()
+                .insert(binding.to_string(), (epoch, holder.clone()));
+        }
+        Some(holder)
+    }
+
+    /// Names of every binding reachable from this environment, innermost first
+    /// A shadowed name is only listed once, and names of each environment are sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        names.sort();
+
         if let Some(parent) = &self.parent {
-            return parent.borrow().lookup(binding);
+            for name in parent.borrow().names() {
+                if !self.variables.contains_key(&name) {
+                    names.push(name);
+                }
+            }
         }
 
-        None
+        names
+    }
+
+    /// Saves the bindings of every environment reachable from env, through parents and the closures bound in them
+    /// Assignment changes bindings in parents, and in the environments of closures created before saving
+    pub(crate) fn save_reachable(env: &Rc<RefCell<Self>>) -> Vec<SavedBindings> {
+        let mut saved = vec![];
+        // Environments, lists, and pairs already visited, so shared ones are only walked once
+        let mut seen: HashSet<*const ()> = HashSet::new();
+        let mut envs = vec![env.clone()];
+        let mut values = vec![];
+        while let Some(env) = envs.pop() {
+            if !seen.insert(Rc::as_ptr(&env) as *const ()) {
+                continue;
+            }
+            let this = env.borrow();
+            saved.push(SavedBindings {
+                env: env.clone(),
+                variables: this.variables.clone(),
+                constants: this.constants.clone(),
+            });
+            envs.extend(this.parent.clone());
+
+            values.extend(this.variables.values().cloned());
+            while let Some(value) = values.pop() {
+                match value {
+                    Expr::Function(Function::Function { env, .. }) => envs.push(env),
+                    Expr::List(list) if seen.insert(Rc::as_ptr(&list) as *const ()) => {
+                        values.extend(list.iter().cloned());
+                    }
+                    Expr::Pair(pair) if seen.insert(Rc::as_ptr(&pair) as *const ()) => {
+                        values.push(pair.head.clone());
+                        values.push(pair.rest.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        saved
+    }
+
+    /// Names bound after the default environment, such as by the program in a REPL session
+    pub fn session_names(&self) -> Vec<String> {
+        let Some(parent) = &self.parent else {
+            // The default environment is the only one without a parent
+            return vec![];
+        };
+
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        names.sort();
+        for name in parent.borrow().session_names() {
+            if !self.variables.contains_key(&name) {
+                names.push(name);
+            }
+        }
+
+        names
+    }
+
+    /// Error for an identifier that is not bound, suggesting the closest name that is
+    pub fn undefined_error(&self, identifier: &str) -> InterpError {
+        // Allow one edit for every (started) three characters, a swap of two characters is two edits
+        let max_distance = identifier.chars().count().div_ceil(3);
+        let suggestion = self
+            .names()
+            .into_iter()
+            .map(|name| (edit_distance(identifier, &name), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            // names() lists the innermost bindings first, which are kept on ties
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name);
+
+        InterpError::UndefinedError {
+            symbol: identifier.to_string(),
+            suggestion,
+        }
     }
 
     /// Look for binding and change it if possible, else return an error
     /// Returns the new value if it was successful
     pub fn assignment(&mut self, identifier: &str, new_value: &Expr) -> Result<Expr, InterpError> {
+        self.assign(identifier, new_value)
+            .unwrap_or_else(|| Err(self.undefined_error(identifier)))
+    }
+
+    /// Assigns to the innermost binding of identifier, None if it is not bound
+    fn assign(&mut self, identifier: &str, new_value: &Expr) -> Option<Result<Expr, InterpError>> {
         // Try to do in this local environment
         if let Some(expr_mut) = self.variables.get_mut(identifier) {
+            if self.constants.contains(identifier) {
+                return Some(Err(InterpError::ImmutableAssignment {
+                    symbol: identifier.to_string(),
+                }));
+            }
             *expr_mut = new_value.clone();
-            return Ok(new_value.clone());
+            return Some(Ok(new_value.clone()));
         }
 
         // Try recursively through parent environments
-        if let Some(parent) = &self.parent {
-            return parent.borrow_mut().assignment(identifier, new_value);
-        }
-
         // Base case, identifier was never found and there is no parent of this environment
-        Err(InterpError::UndefinedError {
-            symbol: identifier.to_string(),
-        })
+        self.parent
+            .as_ref()
+            .and_then(|parent| parent.borrow_mut().assign(identifier, new_value))
     }
 }
 
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances from the prefix of a seen so far to every prefix of b
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 impl Environment {
-    pub fn add_output(&mut self, output: &str) {
+    /// Marker stored after the output that fits in the limit
+    pub const TRUNCATION_MARKER: &'static str = "\n[output truncated]\n";
+
+    /// Stores output, or returns an error once the output limit is exceeded
+    /// Only the part of the output that fits is stored, followed by the truncation marker
+    pub fn add_output(&mut self, output: &str) -> Result<(), InterpError> {
+        let limit = self.output_limit.unwrap_or(usize::MAX);
+        // The limit was already exceeded, the marker is only stored once
+        if self.output_len > limit {
+            return Err(InterpError::OutputLimitExceeded { limit });
+        }
+
+        if self.output_len + output.len() <= limit {
+            self.output_len += output.len();
+            self.store(output);
+            return Ok(());
+        }
+
+        let mut end = limit - self.output_len;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.store(&output[..end]);
+        self.store(Self::TRUNCATION_MARKER);
+        // Past the limit, whatever the marker's length
+        self.output_len = limit + 1;
+        Err(InterpError::OutputLimitExceeded { limit })
+    }
+
+    fn store(&mut self, output: &str) {
+        if let Some(hook) = &self.output_hook {
+            hook.call(output);
+        }
         self.output.push(output.to_string());
     }
 }
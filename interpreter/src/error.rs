@@ -1,7 +1,10 @@
 use std::{error::Error, fmt};
 
+use crate::environment::Arity;
+
 /// Error types for all errors we may encounter in the interpreter
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum InterpError {
     // General parsing error
     ParseError {
@@ -10,18 +13,57 @@ pub enum InterpError {
     // Argument error with function name and argument mismatch
     ArgumentError {
         func: String,
-        expected: usize, // Expected number of arguments
+        expected: Arity, // Expected number of arguments
         got: usize,      // Got this amount of arguments
     },
     // Symbol undefined such as when searching for identifier
     UndefinedError {
         symbol: String,
+        // Closest bound name, in case of a typo
+        suggestion: Option<String>,
+    },
+    // Assignment to a binding made by const
+    ImmutableAssignment {
+        symbol: String,
     },
     // Type error for when a type is incorrect
     TypeError {
         expected: String,
         found: String,
     },
+    // Argument of a function call that does not match the type annotation of its parameter
+    ParameterTypeError {
+        func: String,
+        param: String,
+        expected: String,
+        found: String,
+    },
+    // Value error for when an argument has the right type but an invalid value
+    ValueError {
+        func: String,
+        message: String,
+    },
+    // Arithmetic error such as an overflow, with the function it happened in
+    ArithmeticError {
+        func: String,
+        message: String,
+    },
+    // Failed assertion in the program
+    AssertionError {
+        message: String,
+    },
+    // Error raised by the program itself
+    UserError {
+        message: String,
+    },
+    // More output was stored than the limit (in bytes) allows
+    OutputLimitExceeded {
+        limit: usize,
+    },
+    // Program or its function applications nested deeper than the limit, which could overflow the stack
+    StackOverflow {
+        limit: usize,
+    },
 }
 
 impl fmt::Display for InterpError {
@@ -35,22 +77,79 @@ impl fmt::Display for InterpError {
                 expected,
                 got,
             } => {
+                let expected = match expected {
+                    Arity::Exact(n) => n.to_string(),
+                    Arity::AtLeast(min) => format!("at least {}", min),
+                    Arity::Between(min, max) => format!("{} to {}", min, max),
+                };
                 write!(
                     f,
                     "Incorrect number of arguments supplied to function '{}': expected {}, got {}",
                     func, expected, got
                 )
             }
-            InterpError::UndefinedError { symbol } => {
-                write!(f, "Undefined symbol '{}'", symbol)
+            InterpError::UndefinedError { symbol, suggestion } => {
+                write!(f, "Undefined symbol '{}'", symbol)?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean '{}'?", suggestion),
+                    None => Ok(()),
+                }
+            }
+            InterpError::ImmutableAssignment { symbol } => {
+                write!(f, "Cannot assign to constant '{}'", symbol)
             }
             InterpError::TypeError { expected, found } => {
                 write!(f, "Type error: expected {}, found {}", expected, found)
             }
+            InterpError::ParameterTypeError {
+                func,
+                param,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Type error: parameter '{}' of function '{}' expected {}, found {}",
+                    param, func, expected, found
+                )
+            }
+            InterpError::ValueError { func, message } => {
+                write!(
+                    f,
+                    "Invalid value supplied to function '{}': {}",
+                    func, message
+                )
+            }
+            InterpError::ArithmeticError { func, message } => {
+                write!(f, "Arithmetic error in function '{}': {}", func, message)
+            }
+            InterpError::AssertionError { message } => {
+                write!(f, "Assertion failed: {}", message)
+            }
+            InterpError::UserError { message } => {
+                write!(f, "Error: {}", message)
+            }
+            InterpError::OutputLimitExceeded { limit } => {
+                write!(f, "Output limit of {} bytes exceeded", limit)
+            }
+            InterpError::StackOverflow { limit } => {
+                write!(f, "Stack overflow: nested more than {} levels deep", limit)
+            }
         }
     }
 }
 
+impl InterpError {
+    /// Whether a try expression can recover from the error
+    /// Exceeding a resource limit is not, a handler would otherwise let the program continue past it
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self,
+            InterpError::OutputLimitExceeded { .. } | InterpError::StackOverflow { .. }
+        )
+    }
+}
+
 impl Error for InterpError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         None
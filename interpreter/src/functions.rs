@@ -1,168 +1,231 @@
-use std::{cell::RefCell, fmt, rc::Rc};
-
-use serde_json::Value;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
 
 use crate::{
-    environment::{Environment, LocalEnvironment},
+    ast::{quoted, Ast, ValueType},
+    environment::{Arity, LocalEnvironment},
     error::InterpError,
-    interpreter::{interpret_block, Expr, Interpreter},
+    interpreter::{interpret_block, Expr, Interpreter, Pair},
 };
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub enum Function {
     // Internal Rust function (holds a function pointer)
     CoreFunction {
         name: String,
-        func: fn(&[Expr], &mut Environment) -> Result<Expr, InterpError>,
+        func: fn(&[Expr], &mut Interpreter) -> Result<Expr, InterpError>,
+        arity: Arity,
+        doc: &'static str,
     },
     // User function defined in the language. It has a name and evaluates to an expression.
     Function {
         name: String,
-        args: Vec<String>,
-        func: Value,
+        // Parameters and block are shared, so cloning a function (such as on every lookup) is cheap
+        args: Rc<Vec<String>>,
+        // Type annotations of the parameters, empty if none are annotated
+        types: Rc<Vec<Option<ValueType>>>,
+        func: Rc<Vec<Ast>>,
         // Copy of the environment from when this function was created (lexical scope)
         env: Rc<RefCell<LocalEnvironment>>,
+        // String literal starting the function's block, if it had one
+        doc: Option<Rc<str>>,
     },
 }
 
-/// Parse a function into a UFunc, without evaluating it
-pub fn parse_anonymous_function(
-    val: &serde_json::Value,
+/// Create a user function from a compiled lambda, without evaluating it
+pub fn create_function(
+    params: &Rc<Vec<String>>,
+    types: &Rc<Vec<Option<ValueType>>>,
+    block: &Rc<Vec<Ast>>,
+    doc: Option<Rc<str>>,
     name: Option<&str>,
     interpreter: &mut Interpreter,
-) -> Result<Expr, InterpError> {
-    // We are in the "Lambda" object's value, which should have two list items, a parameters object and block object
-    let [parameters, block] = match val.as_array() {
-        Some(arr) if arr.len() == 2 => [&arr[0], &arr[1]],
-        _ => {
-            return Err(InterpError::ParseError {
-                message: "Function should have an associated parameters list and block."
-                    .to_string(),
-            })
-        }
-    };
-
-    let parameters: Vec<String> = match parameters
-        .as_object()
-        .and_then(|obj| obj.get("Parameters"))
-        .and_then(|list| list.as_array())
-    {
-        Some(list) => list
-            .iter()
-            .map(|p| {
-                match p
-                    .as_object()
-                    .and_then(|obj| obj.get("Identifier"))
-                    .and_then(|i| i.as_str())
-                {
-                    Some(s) => Ok(s.to_string()),
-                    _ => {
-                        return Err(InterpError::ParseError {
-                            message: "All parameters must be an identifier.".to_string(),
-                        })
-                    }
-                }
-            })
-            .collect::<Result<Vec<String>, InterpError>>()?,
-        _ => {
-            return Err(InterpError::ParseError {
-                message: "Parameters list is missing.".to_string(),
-            })
-        }
-    };
-
-    let block = block
-        .as_object()
-        .and_then(|obj| obj.get("Block"))
-        .ok_or_else(|| {
-            return InterpError::ParseError {
-                message: "Function should contain a block.".to_string(),
-            };
-        })?;
-
-    let local_name = if name.is_some() {
-        name.unwrap()
-    } else {
-        "Anonymous"
-    };
+) -> Expr {
+    let local_name = name.unwrap_or("Anonymous");
 
     let new_env = LocalEnvironment::from_parent(interpreter.local.clone());
 
     let expr = Function::Function {
         name: local_name.to_string(),
-        args: parameters,
+        args: params.clone(),
+        types: types.clone(),
         func: block.clone(),
         env: new_env,
+        doc,
     };
 
-    Ok(Expr::Function(expr))
+    Expr::Function(expr)
 }
 
+/// Evaluates an application in a fixed order: the function first, then the arguments from left to right
+/// Evaluation stops at the first error, so no argument is evaluated if the first expression is not a function
 pub fn function_application(
-    val: &serde_json::Value,
+    arr: &[Ast],
+    interpreter: &mut Interpreter,
+) -> Result<Expr, InterpError> {
+    let (first, rest) = arr.split_first().ok_or(InterpError::ParseError {
+        message: "Function application on nothing.".to_string(),
+    })?;
+    let func = match Expr::eval_ast(first, interpreter)? {
+        Expr::Function(func) => func,
+        other => {
+            return Err(InterpError::TypeError {
+                expected: "function".to_string(),
+                found: other.to_string(),
+            })
+        }
+    };
+    let args = rest
+        .iter()
+        .map(|ast| Expr::eval_ast(ast, interpreter))
+        .collect::<Result<Vec<Expr>, InterpError>>()?;
+    apply_function(&func, &args, interpreter)
+}
+
+/// Calls a function with already evaluated arguments, also used by builtins which take functions
+pub fn apply_function(
+    func: &Function,
+    rest: &[Expr],
+    interpreter: &mut Interpreter,
+) -> Result<Expr, InterpError> {
+    // Recursion too deep would overflow the native stack, which crashes instead of returning an error
+    if interpreter.call_depth >= Interpreter::MAX_CALL_DEPTH {
+        return Err(InterpError::StackOverflow {
+            limit: Interpreter::MAX_CALL_DEPTH,
+        });
+    }
+    interpreter.stats.applications += 1;
+    interpreter.call_depth += 1;
+    interpreter.stats.max_depth = interpreter.stats.max_depth.max(interpreter.call_depth);
+    let result = call_function(func, rest, interpreter);
+    interpreter.call_depth -= 1;
+    result
+}
+
+/// Calls a function, apply_function keeps the stats of the call
+fn call_function(
+    func: &Function,
+    rest: &[Expr],
     interpreter: &mut Interpreter,
 ) -> Result<Expr, InterpError> {
-    if let Expr::List(list) = Expr::eval(val, interpreter)? {
-        let (first, rest) = list.split_first().ok_or(InterpError::ParseError {
-            message: "Function application on nothing.".to_string(),
-        })?;
-        if let Expr::Function(func) = first {
-            match func {
-                Function::CoreFunction { name: _name, func } => {
-                    return func(rest, &mut interpreter.global)
+    match func {
+        Function::CoreFunction {
+            name, func, arity, ..
+        } => {
+            // Checked here so every builtin reports wrong argument counts the same way
+            arity.check(name, rest.len())?;
+            func(rest, interpreter)
+        }
+        Function::Function {
+            name,
+            args,
+            types,
+            func,
+            env: local_env,
+            ..
+        } => {
+            if args.len() != rest.len() {
+                return Err(InterpError::ArgumentError {
+                    func: name.to_string(),
+                    expected: Arity::Exact(args.len()),
+                    got: rest.len(),
+                });
+            }
+            for ((param, value_type), arg) in args.iter().zip(types.iter()).zip(rest) {
+                match value_type {
+                    Some(value_type) if !value_type.matches(arg) => {
+                        return Err(InterpError::ParameterTypeError {
+                            func: name.to_string(),
+                            param: param.to_string(),
+                            expected: value_type.to_string(),
+                            found: arg.repr(),
+                        })
+                    }
+                    _ => {}
                 }
+            }
+
+            // On lexical scope (default), functions use environment of where the function was originating from.
+            if interpreter.global.lexical_scope {
+                let current_local = interpreter.enter_local(local_env.clone());
+                let result = interpret_block(
+                    func,
+                    interpreter,
+                    Some(args.iter().zip(rest).collect::<Vec<(&String, &Expr)>>()),
+                );
+                // Pop environment
+                interpreter.local = current_local;
+                result
+            } else {
+                // On dynamic scope, the parameters are bound in a child of the caller's environment,
+                // so free variables are looked up through the caller's chain instead of the definition site.
+                interpret_block(
+                    func,
+                    interpreter,
+                    Some(args.iter().zip(rest).collect::<Vec<(&String, &Expr)>>()),
+                )
+            }
+        }
+    }
+}
+
+impl Function {
+    /// Number of arguments the function takes, None for builtins taking a varying number of them
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            Function::CoreFunction {
+                arity: Arity::Exact(n),
+                ..
+            } => Some(*n),
+            Function::CoreFunction { .. } => None,
+            Function::Function { args, .. } => Some(args.len()),
+        }
+    }
+}
+
+// Core functions are compared by name, function pointer comparisons are not meaningful
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Function::CoreFunction { name, .. },
+                Function::CoreFunction {
+                    name: other_name, ..
+                },
+            ) => name == other_name,
+            (
                 Function::Function {
                     name,
                     args,
+                    types,
                     func,
-                    env: local_env,
-                } => {
-                    if args.len() != rest.len() {
-                        return Err(InterpError::ArgumentError {
-                            func: name.to_string(),
-                            expected: args.len(),
-                            got: rest.len(),
-                        });
-                    }
-
-                    // On lexical scope (default), functions use environment of where the function was originating from.
-                    if interpreter.global.lexical_scope {
-                        let current_local = interpreter.enter_local(local_env.clone());
-                        let result = interpret_block(
-                            &func,
-                            interpreter,
-                            Some(
-                                args.into_iter()
-                                    .zip(rest.into_iter())
-                                    .collect::<Vec<(&String, &Expr)>>(),
-                            ),
-                        );
-                        // Pop environment
-                        interpreter.local = current_local;
-                        return result;
-                    } else {
-                        return interpret_block(
-                            &func,
-                            interpreter,
-                            Some(
-                                args.into_iter()
-                                    .zip(rest.into_iter())
-                                    .collect::<Vec<(&String, &Expr)>>(),
-                            ),
-                        );
-                    }
-                }
+                    env,
+                    doc,
+                },
+                Function::Function {
+                    name: other_name,
+                    args: other_args,
+                    types: other_types,
+                    func: other_func,
+                    env: other_env,
+                    doc: other_doc,
+                },
+            ) => {
+                name == other_name
+                    && args == other_args
+                    && types == other_types
+                    && func == other_func
+                    && env == other_env
+                    && doc == other_doc
             }
-        } else {
-            return Err(InterpError::TypeError {
-                expected: "function".to_string(),
-                found: first.to_string(),
-            });
+            _ => false,
         }
     }
-    Err(InterpError::ParseError {
-        message: "Expected function and arguments.".to_string(),
-    })
 }
 
 impl fmt::Debug for Function {
@@ -170,10 +233,7 @@ impl fmt::Debug for Function {
         match self {
             Function::CoreFunction { name, .. } => write!(f, "CoreFunction(name: {})", name),
             Function::Function {
-                name,
-                args,
-                func,
-                env: _,
+                name, args, func, ..
             } => {
                 write!(
                     f,
@@ -187,66 +247,348 @@ impl fmt::Debug for Function {
 
 /// Helper functions
 fn exprs_into_i64(args: &[Expr]) -> Result<Vec<i64>, InterpError> {
-    args.into_iter()
+    args.iter()
         .map(|expr| expr.clone().try_into())
         .collect::<Result<Vec<i64>, InterpError>>()
 }
 
-/// BEGIN INBUILT FUNCTIONS
+// BEGIN INBUILT FUNCTIONS
 
 // Takes in any amount of arguments and adds them together
-pub fn add(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn add(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let ints = exprs_into_i64(args)?;
-    Ok(Expr::Integer(ints.into_iter().sum()))
+    ints.into_iter()
+        .try_fold(0, i64::checked_add)
+        .map(Expr::Integer)
+        .ok_or_else(|| overflow_error("add"))
 }
 
 // Takes in any amount of arguments and subtracts from the first argument
-pub fn sub(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn sub(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let ints = exprs_into_i64(args)?;
-    Ok(Expr::Integer(
-        ints.into_iter().reduce(|first, x| first - x).unwrap_or(0),
-    ))
+    match ints.split_first() {
+        Some((first, rest)) => rest
+            .iter()
+            .try_fold(*first, |acc, x| acc.checked_sub(*x))
+            .map(Expr::Integer)
+            .ok_or_else(|| overflow_error("sub")),
+        None => Ok(Expr::Integer(0)),
+    }
 }
 
 // Takes in any amount of arguments and multiplies by the first argument
-pub fn mul(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn mul(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let ints = exprs_into_i64(args)?;
-    Ok(Expr::Integer(ints.into_iter().product()))
+    ints.into_iter()
+        .try_fold(1, i64::checked_mul)
+        .map(Expr::Integer)
+        .ok_or_else(|| overflow_error("mul"))
 }
 
 // divides first argument by second
-pub fn div(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn div(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let ints = exprs_into_i64(args)?;
-    if ints.len() != 2 {
-        return Err(InterpError::ArgumentError {
-            func: "div".to_string(),
-            expected: 2,
+    ints[0]
+        .checked_div(ints[1])
+        .map(Expr::Integer)
+        .ok_or_else(|| division_error("div", ints[1]))
+}
+
+// gets remainder of first argument by second
+pub fn rem(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ints = exprs_into_i64(args)?;
+    ints[0]
+        .checked_rem(ints[1])
+        .map(Expr::Integer)
+        .ok_or_else(|| division_error("rem", ints[1]))
+}
+
+/// Euclidean modulo of the first argument by the second, never negative unlike rem
+/// mod(-1, 3) is 2 where rem(-1, 3) is -1
+pub fn modulo(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (dividend, divisor) = int_pair("mod", args)?;
+    if divisor == 0 {
+        return Err(division_error("mod", divisor));
+    }
+    // Only i64::MIN by -1 wraps, and its modulo is 0 which cannot overflow
+    Ok(Expr::Integer(dividend.wrapping_rem_euclid(divisor)))
+}
+
+/// Error for a failed integer division, by zero or overflowing (i64::MIN by -1)
+fn division_error(func: &str, divisor: i64) -> InterpError {
+    match divisor {
+        0 => InterpError::ArithmeticError {
+            func: func.to_string(),
+            message: "division by zero".to_string(),
+        },
+        _ => overflow_error(func),
+    }
+}
+
+/// Error for integer arithmetic whose result does not fit in an i64
+fn overflow_error(func: &str) -> InterpError {
+    InterpError::ArithmeticError {
+        func: func.to_string(),
+        message: "result overflowed".to_string(),
+    }
+}
+
+/// Greatest common divisor of two integers using the Euclidean algorithm
+/// gcd(0, 0) is defined as 0
+pub fn gcd(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ints = exprs_into_i64(args)?;
+    let divisor = gcd_u64(ints[0].unsigned_abs(), ints[1].unsigned_abs());
+    // Only gcd(i64::MIN, 0) and gcd(i64::MIN, i64::MIN) do not fit back into an i64
+    i64::try_from(divisor)
+        .map(Expr::Integer)
+        .map_err(|_| InterpError::ArithmeticError {
+            func: "gcd".to_string(),
+            message: "result overflowed".to_string(),
+        })
+}
+
+/// Least common multiple of two integers, always non-negative
+/// lcm with 0 is 0
+pub fn lcm(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ints = exprs_into_i64(args)?;
+    let (a, b) = (ints[0].unsigned_abs(), ints[1].unsigned_abs());
+    if a == 0 || b == 0 {
+        return Ok(Expr::Integer(0));
+    }
+    (a / gcd_u64(a, b))
+        .checked_mul(b)
+        .and_then(|multiple| i64::try_from(multiple).ok())
+        .map(Expr::Integer)
+        .ok_or_else(|| InterpError::ArithmeticError {
+            func: "lcm".to_string(),
+            message: "result overflowed".to_string(),
+        })
+}
+
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Bitwise and of two integers
+pub fn bitand(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (a, b) = int_pair("bitand", args)?;
+    Ok(Expr::Integer(a & b))
+}
+
+/// Bitwise or of two integers
+pub fn bitor(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (a, b) = int_pair("bitor", args)?;
+    Ok(Expr::Integer(a | b))
+}
+
+/// Bitwise exclusive or of two integers
+pub fn bitxor(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (a, b) = int_pair("bitxor", args)?;
+    Ok(Expr::Integer(a ^ b))
+}
+
+/// Shifts the first argument left by the second, bits shifted past the top are dropped
+pub fn shl(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (value, amount) = int_pair("shl", args)?;
+    Ok(Expr::Integer(value << shift_amount("shl", amount)?))
+}
+
+/// Shifts the first argument right by the second, keeping the sign (-8 to -4 for a shift of 1)
+pub fn shr(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (value, amount) = int_pair("shr", args)?;
+    Ok(Expr::Integer(value >> shift_amount("shr", amount)?))
+}
+
+fn int_pair(func: &str, args: &[Expr]) -> Result<(i64, i64), InterpError> {
+    match exprs_into_i64(args)?.as_slice() {
+        [a, b] => Ok((*a, *b)),
+        ints => Err(InterpError::ArgumentError {
+            func: func.to_string(),
+            expected: Arity::Exact(2),
             got: ints.len(),
+        }),
+    }
+}
+
+/// Shifts are only defined for amounts from 0 up to the number of bits in an integer
+fn shift_amount(func: &str, amount: i64) -> Result<u32, InterpError> {
+    u32::try_from(amount)
+        .ok()
+        .filter(|amount| *amount < i64::BITS)
+        .ok_or_else(|| InterpError::ValueError {
+            func: func.to_string(),
+            message: format!("shift amount {} is not between 0 and 63", amount),
+        })
+}
+
+/// Converts an integer to a float, floats are returned unchanged
+pub fn to_float(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("to_float", args)? {
+        Expr::Integer(i) => Ok(Expr::Float(*i as f64)),
+        Expr::Float(f) => Ok(Expr::Float(*f)),
+        arg => Err(InterpError::TypeError {
+            expected: "integer or float".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Converts a float (truncating towards zero) or a string of digits to an integer, integers are returned unchanged
+pub fn to_int(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("to_int", args)? {
+        Expr::Integer(i) => Ok(Expr::Integer(*i)),
+        Expr::Float(f) => float_to_int("to_int", f.trunc()),
+        Expr::String(s) => {
+            s.trim()
+                .parse()
+                .map(Expr::Integer)
+                .map_err(|_| InterpError::ValueError {
+                    func: "to_int".to_string(),
+                    message: format!("\"{}\" is not an integer", s),
+                })
+        }
+        arg => Err(InterpError::TypeError {
+            expected: "float, integer, or string".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Largest integer less than or equal to a number
+pub fn floor(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    round_with("floor", args, f64::floor)
+}
+
+/// Smallest integer greater than or equal to a number
+pub fn ceil(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    round_with("ceil", args, f64::ceil)
+}
+
+/// Nearest integer to a number, halves round away from zero (2.5 to 3, -2.5 to -3)
+pub fn round(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    round_with("round", args, f64::round)
+}
+
+/// Rounds a float to an integer with the given rounding function, integers are returned unchanged
+fn round_with(func: &str, args: &[Expr], rounding: fn(f64) -> f64) -> Result<Expr, InterpError> {
+    match single_arg(func, args)? {
+        Expr::Integer(i) => Ok(Expr::Integer(*i)),
+        Expr::Float(f) => float_to_int(func, rounding(*f)),
+        arg => Err(InterpError::TypeError {
+            expected: "integer or float".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Converts a whole float to an integer, erroring on NaN, infinity, or values out of range
+fn float_to_int(func: &str, f: f64) -> Result<Expr, InterpError> {
+    // i64::MAX as f64 rounds up to 2^63, which is itself out of range
+    if f.is_finite() && f >= i64::MIN as f64 && f < i64::MAX as f64 {
+        Ok(Expr::Integer(f as i64))
+    } else {
+        Err(InterpError::ValueError {
+            func: func.to_string(),
+            message: format!("{:?} does not fit in an integer", f),
+        })
+    }
+}
+
+/// The only argument of a function which takes one
+fn single_arg<'a>(func: &str, args: &'a [Expr]) -> Result<&'a Expr, InterpError> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(InterpError::ArgumentError {
+            func: func.to_string(),
+            expected: Arity::Exact(1),
+            got: args.len(),
+        }),
+    }
+}
+
+/// Most calls of eval that can run inside of each other, like a program evaluating itself
+#[cfg(feature = "parser")]
+const MAX_EVAL_DEPTH: usize = 64;
+
+/// Parses and evaluates a string of source in the current environment, returning its value
+#[cfg(feature = "parser")]
+pub fn eval(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let source: String = single_arg("eval", args)?.clone().try_into()?;
+    if interpreter.global.eval_depth >= MAX_EVAL_DEPTH {
+        return Err(InterpError::ValueError {
+            func: "eval".to_string(),
+            message: format!(
+                "more than {} calls of eval inside of each other",
+                MAX_EVAL_DEPTH
+            ),
         });
     }
-    Ok(Expr::Integer(ints[0] / ints[1]))
+
+    let ast = parser::parse("eval", &source).map_err(|e| InterpError::ParseError {
+        message: format!("{} {} here", e, e.label()),
+    })?;
+    interpreter.global.eval_depth += 1;
+    let result = Expr::eval(&ast, interpreter);
+    interpreter.global.eval_depth -= 1;
+    result
 }
 
-// gets remainder of first argument by second
-pub fn rem(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+/// Random integer from the first argument (inclusive) up to the second argument (exclusive)
+pub fn random(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let ints = exprs_into_i64(args)?;
-    if ints.len() != 2 {
-        return Err(InterpError::ArgumentError {
-            func: "rem".to_string(),
-            expected: 2,
-            got: ints.len(),
+    if ints[0] >= ints[1] {
+        return Err(InterpError::ValueError {
+            func: "random".to_string(),
+            message: format!("range {} to {} is empty", ints[0], ints[1]),
         });
     }
-    Ok(Expr::Integer(ints[0] % ints[1]))
+    Ok(Expr::Integer(
+        interpreter.global.rng.range(ints[0], ints[1]),
+    ))
+}
+
+/// Milliseconds elapsed since the interpreter was created
+#[cfg(not(target_arch = "wasm32"))]
+pub fn clock(_args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let millis = interpreter.global.start.elapsed().as_millis();
+    Ok(Expr::Integer(i64::try_from(millis).unwrap_or(i64::MAX)))
+}
+
+/// Negation of a boolean
+pub fn not(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(Expr::Boolean(!single_arg("not", args)?.is_truthy()?))
+}
+
+/// Whether every argument is true, all arguments are evaluated (and must be booleans) before the call
+pub fn and(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let bools = args
+        .iter()
+        .map(Expr::is_truthy)
+        .collect::<Result<Vec<bool>, InterpError>>()?;
+    Ok(Expr::Boolean(bools.into_iter().all(|b| b)))
+}
+
+/// Whether any argument is true, all arguments are evaluated (and must be booleans) before the call
+pub fn or(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let bools = args
+        .iter()
+        .map(Expr::is_truthy)
+        .collect::<Result<Vec<bool>, InterpError>>()?;
+    Ok(Expr::Boolean(bools.into_iter().any(|b| b)))
 }
 
-pub fn zero(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn zero(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let int = exprs_into_i64(args)?;
     let bool = int[0] == 0;
     Ok(Expr::Boolean(bool))
 }
 
-pub fn eq(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+// Checks that all arguments are equal to the first
+// Integers and floats compare by numeric value, so equal?(1, 1.0) is true, while NaN is not equal to itself
+pub fn eq(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     match args.is_empty() {
         false => {
             let first = args.first().expect("Was not empty in previous check");
@@ -256,202 +598,772 @@ pub fn eq(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError>
     }
 }
 
-pub fn greater(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    let ints = exprs_into_i64(args)?;
-    if ints.len() != 2 {
+/// Errors unless the argument is true
+pub fn assert(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("assert", args)? {
+        Expr::Boolean(true) => Ok(Expr::Boolean(true)),
+        arg => Err(InterpError::AssertionError {
+            message: format!("expected true, found {}", arg.display_escaped()),
+        }),
+    }
+}
+
+/// Errors unless both arguments are equal (as compared by equal?)
+pub fn assert_equal(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let [left, right] = args else {
         return Err(InterpError::ArgumentError {
-            func: "greater?".to_string(),
-            expected: 2,
-            got: ints.len(),
+            func: "assert_equal".to_string(),
+            expected: Arity::Exact(2),
+            got: args.len(),
+        });
+    };
+    if left != right {
+        // Strings are quoted so that 1 and "1" can be told apart
+        return Err(InterpError::AssertionError {
+            message: format!(
+                "values are not equal\n  left: {}\n right: {}",
+                left.repr(),
+                right.repr()
+            ),
         });
     }
-    Ok(Expr::Boolean(ints[0] > ints[1]))
+    Ok(Expr::Boolean(true))
 }
 
-pub fn less(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    let ints = exprs_into_i64(args)?;
-    if ints.len() != 2 {
+/// Raises an error with the given message, which can be caught by try
+pub fn error(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let message: String = single_arg("error", args)?.clone().try_into()?;
+    Err(InterpError::UserError { message })
+}
+
+// Numbers compare by value, strings by their characters, and lists element by element
+pub fn greater(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ordering = compare_pair("greater?", args)?;
+    Ok(Expr::Boolean(ordering == Some(Ordering::Greater)))
+}
+
+pub fn less(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ordering = compare_pair("less?", args)?;
+    Ok(Expr::Boolean(ordering == Some(Ordering::Less)))
+}
+
+/// Orders two values as -1, 0, or 1 for less, equal, or greater, with the same rules as less? and greater?
+pub fn compare_values(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match compare_pair("compare", args)? {
+        Some(ordering) => Ok(Expr::Integer(ordering as i64)),
+        None => Err(InterpError::ValueError {
+            func: "compare".to_string(),
+            message: format!("{} and {} cannot be ordered", args[0], args[1]),
+        }),
+    }
+}
+
+/// Compares the two arguments of a comparison function
+fn compare_pair(func: &str, args: &[Expr]) -> Result<Option<Ordering>, InterpError> {
+    let [left, right] = args else {
         return Err(InterpError::ArgumentError {
-            func: "less?".to_string(),
-            expected: 2,
-            got: ints.len(),
+            func: func.to_string(),
+            expected: Arity::Exact(2),
+            got: args.len(),
         });
-    }
-    Ok(Expr::Boolean(ints[0] < ints[1]))
+    };
+    compare(left, right)
 }
 
-pub fn print(args: &[Expr], global: &mut Environment) -> Result<Expr, InterpError> {
-    for arg in args {
-        if global.store_output {
-            global.add_output(&arg.to_string());
-        } else {
-            print!("{}", arg);
+/// Orders two values of comparable types, None if they cannot be ordered (a NaN float)
+/// Lists are ordered lexicographically, where a list is less than any longer list it is the start of
+fn compare(left: &Expr, right: &Expr) -> Result<Option<Ordering>, InterpError> {
+    match (left, right) {
+        (Expr::Integer(a), Expr::Integer(b)) => Ok(Some(a.cmp(b))),
+        (Expr::Integer(a), Expr::Float(b)) => Ok(compare_int_float(*a, *b)),
+        (Expr::Float(a), Expr::Integer(b)) => Ok(compare_int_float(*b, *a).map(Ordering::reverse)),
+        (Expr::Float(a), Expr::Float(b)) => Ok(a.partial_cmp(b)),
+        (Expr::String(a), Expr::String(b)) => Ok(Some(a.cmp(b))),
+        (Expr::Char(a), Expr::Char(b)) => Ok(Some(a.cmp(b))),
+        (Expr::List(a), Expr::List(b)) => {
+            for (a, b) in a.iter().zip(b.iter()) {
+                match compare(a, b)? {
+                    Some(Ordering::Equal) => continue,
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(Some(a.len().cmp(&b.len())))
         }
+        _ => Err(InterpError::TypeError {
+            expected: "two numbers, strings, or lists".to_string(),
+            found: format!("{} and {}", left, right),
+        }),
     }
+}
 
-    Ok(Expr::Boolean(true))
+/// Orders an integer and a float exactly, converting the integer to f64 could round it to equal the float
+fn compare_int_float(int: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    // Outside of the i64 range (including infinities) the float is beyond every integer
+    if float >= 9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Less);
+    }
+    if float < -9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Greater);
+    }
+    let whole = float.trunc();
+    // The fraction is not NaN, so comparing it with zero always has a result
+    let fraction = float - whole;
+    Some(int.cmp(&(whole as i64)).then(0f64.partial_cmp(&fraction)?))
 }
 
-pub fn println(args: &[Expr], global: &mut Environment) -> Result<Expr, InterpError> {
-    let mut str = String::new();
+pub fn print(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     for arg in args {
-        if global.store_output {
-            str.push_str(&mut arg.to_string());
+        if interpreter.global.store_output {
+            interpreter.global.add_output(&arg.to_string())?;
         } else {
-            print!("{}", arg);
+            print_flushed(&arg.to_string());
         }
     }
 
-    // Add newline
-    if global.store_output {
-        str.push('\n');
-        global.add_output(&str);
+    Ok(Expr::Boolean(true))
+}
+
+// Unlike print, separates the arguments with spaces
+pub fn println(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let line = println_line(args);
+
+    // Both modes write the same line, so captured output matches what is printed
+    if interpreter.global.store_output {
+        interpreter.global.add_output(&line)?;
     } else {
-        println!();
+        print_flushed(&line);
     }
 
     Ok(Expr::Boolean(true))
 }
 
-pub fn dbg(args: &[Expr], global: &mut Environment) -> Result<Expr, InterpError> {
+/// Prints to stdout and flushes, so output is not held back behind a later error written to stderr
+fn print_flushed(text: &str) {
+    print!("{}", text);
+    // Like print!, which panics if stdout cannot be written, a failed flush has nowhere to be reported
+    let _ = io::stdout().flush();
+}
+
+/// Line written by println, ending with exactly one newline
+fn println_line(args: &[Expr]) -> String {
+    let mut line = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    line.push('\n');
+    line
+}
+
+// Prints the argument like println and returns it, so a value can be printed in the middle of a pipeline
+pub fn inspect(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let arg = single_arg("inspect", args)?;
+    println(std::slice::from_ref(arg), interpreter)?;
+    Ok(arg.clone())
+}
+
+// Prints the debug form of each argument on its own line, to stderr unless output is stored
+pub fn dbg(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     for arg in args {
-        if global.store_output {
-            global.add_output(format!("{:#?}\n", arg).as_str());
+        let line = dbg_line(arg);
+        // Both modes write the same line, without the source location the dbg! macro would add
+        if interpreter.global.store_output {
+            interpreter.global.add_output(&line)?;
         } else {
-            dbg!(arg);
+            eprint!("{}", line);
         }
     }
     Ok(Expr::Boolean(true))
 }
 
+/// The line dbg writes for a value
+fn dbg_line(arg: &Expr) -> String {
+    format!("{:#?}\n", arg)
+}
+
+/// Returns the argument as a string with control characters shown as escapes, such as "\n"
+pub fn escape(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(Expr::String(args[0].display_escaped()))
+}
+
 /// Returns argument strings as new, uppercase strings
 /// If there are multiple arguments, it returns a list of the new strings
-pub fn to_uppercase(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn to_uppercase(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     if args.len() > 1 {
         let exprs = args
-            .into_iter()
-            .map(|f| {
-                f.clone()
-                    .try_into()
-                    .and_then(|s: String| Ok(s.to_uppercase()))
-            })
+            .iter()
+            .map(|f| f.clone().try_into().map(|s: String| s.to_uppercase()))
             .collect::<Result<Vec<String>, InterpError>>()?;
-        Ok(Expr::List(
-            exprs.into_iter().map(|s| Expr::String(s)).collect(),
-        ))
+        Ok(Expr::List(Rc::new(
+            exprs.into_iter().map(Expr::String).collect(),
+        )))
     } else {
         Ok(Expr::String(
             args[0]
                 .clone()
                 .try_into()
-                .and_then(|s: String| Ok(s.to_uppercase()))?,
+                .map(|s: String| s.to_uppercase())?,
         ))
     }
 }
 
 /// Returns argument strings as new, lowercase strings
 /// If there are multiple arguments, it returns a list of the new strings
-pub fn to_lowercase(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn to_lowercase(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     if args.len() > 1 {
         let exprs = args
-            .into_iter()
-            .map(|f| {
-                f.clone()
-                    .try_into()
-                    .and_then(|s: String| Ok(s.to_lowercase()))
-            })
+            .iter()
+            .map(|f| f.clone().try_into().map(|s: String| s.to_lowercase()))
             .collect::<Result<Vec<String>, InterpError>>()?;
-        Ok(Expr::List(
-            exprs.into_iter().map(|s| Expr::String(s)).collect(),
-        ))
+        Ok(Expr::List(Rc::new(
+            exprs.into_iter().map(Expr::String).collect(),
+        )))
     } else {
         Ok(Expr::String(
             args[0]
                 .clone()
                 .try_into()
-                .and_then(|s: String| Ok(s.to_lowercase()))?,
+                .map(|s: String| s.to_lowercase())?,
         ))
     }
 }
 
 /// Concatenates strings together
-pub fn concat(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn concat(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     let exprs = args
-        .into_iter()
+        .iter()
         .map(|f| f.clone().try_into())
         .collect::<Result<Vec<String>, InterpError>>()?;
     Ok(Expr::String(exprs.concat()))
 }
 
-/// Checks if a string contains a character
-/// First argument is the character to check if the rest of the arguments contain
-pub fn contains(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    let exprs = args
-        .into_iter()
-        .map(|f| f.clone().try_into())
-        .collect::<Result<Vec<String>, InterpError>>()?;
-
-    let first_arg = exprs.first();
-    let rest = &exprs[1..];
-    if first_arg.is_none() || rest.len() == 0 {
+/// Substitutes the arguments after the template string for its "{}" placeholders in order
+/// "{{" and "}}" are literal braces
+pub fn format(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Some((template, values)) = args.split_first() else {
         return Err(InterpError::ArgumentError {
-            func: "contains".to_string(),
-            expected: 2,
+            func: "format".to_string(),
+            expected: Arity::Exact(1),
             got: 0,
         });
-    } else {
-        let first_arg = first_arg.unwrap();
-        return Ok(Expr::Boolean(
-            !rest.iter().any(|ele| !ele.contains(first_arg)),
-        ));
+    };
+    let template: String = template.clone().try_into()?;
+    let format_error = |message: String| InterpError::ValueError {
+        func: "format".to_string(),
+        message,
+    };
+
+    let mut formatted = String::with_capacity(template.len());
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                formatted.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(value) = values.get(placeholders) {
+                    formatted.push_str(&value.to_string());
+                }
+                placeholders += 1;
+            }
+            ('{' | '}', _) => {
+                return Err(format_error(format!(
+                    "unmatched '{}' in template, use '{}{}' for a literal brace",
+                    c, c, c
+                )))
+            }
+            _ => formatted.push(c),
+        }
     }
-}
 
-// Returns all the arguments as a list expression
-pub fn as_list(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    Ok(Expr::List(args.to_vec()))
+    if placeholders != values.len() {
+        return Err(format_error(format!(
+            "template has {} placeholder(s) but {} value(s) were given",
+            placeholders,
+            values.len()
+        )));
+    }
+    Ok(Expr::String(formatted))
 }
 
-// Returns the expression at the provided index of a list
-// First arg: list expr
-// Second arg: idx
-pub fn get(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    if args.len() != 2 {
-        return Err(InterpError::ArgumentError {
-            func: "get".to_string(),
-            expected: 2,
-            got: args.len(),
-        });
-    }
-    let idx: &i64 = &args[1].clone().try_into()?;
-    if let Expr::List(list) = &args[0] {
-        Ok(list[*idx as usize].clone())
-    } else {
-        Err(InterpError::TypeError {
-            expected: "list".to_string(),
-            found: args[0].to_string(),
-        })
+/// Serializes a value to a JSON string, nil is null and lists are arrays
+pub fn to_json(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    fn json_value(expr: &Expr) -> Result<serde_json::Value, InterpError> {
+        match expr {
+            Expr::Integer(int) => Ok((*int).into()),
+            Expr::Float(float) => serde_json::Number::from_f64(*float)
+                .map(serde_json::Value::Number)
+                .ok_or_else(|| InterpError::ValueError {
+                    func: "to_json".to_string(),
+                    message: format!("{} has no JSON representation", float),
+                }),
+            Expr::Boolean(bool) => Ok((*bool).into()),
+            Expr::String(string) => Ok(string.as_str().into()),
+            Expr::Char(c) => Ok(c.to_string().into()),
+            Expr::List(list) => list.iter().map(json_value).collect(),
+            Expr::Pair(pair) => pair.iter().map(json_value).collect(),
+            Expr::Nil => Ok(serde_json::Value::Null),
+            Expr::Function(_) => Err(InterpError::TypeError {
+                expected: "data value".to_string(),
+                found: expr.to_string(),
+            }),
+        }
+    }
+    Ok(Expr::String(
+        json_value(single_arg("to_json", args)?)?.to_string(),
+    ))
+}
+
+/// Parses a JSON string into a value, null is nil and arrays are lists
+/// Objects are an error, there is no map type to hold them without changing their shape
+pub fn from_json(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    fn json_expr(val: &serde_json::Value) -> Result<Expr, InterpError> {
+        match val {
+            serde_json::Value::Array(arr) => Ok(Expr::List(Rc::new(
+                arr.iter().map(json_expr).collect::<Result<_, _>>()?,
+            ))),
+            serde_json::Value::Object(_) => Err(InterpError::ValueError {
+                func: "from_json".to_string(),
+                message: format!("objects are not supported: {}", val),
+            }),
+            _ => Ok(quoted(val)),
+        }
+    }
+
+    let json: String = single_arg("from_json", args)?.clone().try_into()?;
+    let val = serde_json::from_str(&json).map_err(|e| InterpError::ValueError {
+        func: "from_json".to_string(),
+        message: format!("malformed JSON: {}", e),
+    })?;
+    json_expr(&val)
+}
+
+/// Replaces occurrences of a pattern in a string, returning the new string
+/// First arg: string to search
+/// Second arg: pattern (not empty)
+/// Third arg: replacement
+/// Optional fourth arg: true to only replace the first occurrence (default false)
+pub fn string_replace(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let strings = args[..3]
+        .iter()
+        .map(|f| f.clone().try_into())
+        .collect::<Result<Vec<String>, InterpError>>()?;
+    let first_only: bool = match args.get(3) {
+        Some(arg) => arg.clone().try_into()?,
+        None => false,
+    };
+
+    let [haystack, pattern, replacement] = strings.as_slice() else {
+        unreachable!("Exactly three strings were collected");
+    };
+    if pattern.is_empty() {
+        return Err(InterpError::ValueError {
+            func: "string_replace".to_string(),
+            message: "pattern cannot be empty".to_string(),
+        });
+    }
+
+    if first_only {
+        Ok(Expr::String(haystack.replacen(pattern, replacement, 1)))
+    } else {
+        Ok(Expr::String(haystack.replace(pattern, replacement)))
+    }
+}
+
+/// Unicode scalar value of a character, or of a single character string
+pub fn char_code(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let string: String = args[0].clone().try_into()?;
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Expr::Integer(c as i64)),
+        _ => Err(InterpError::ValueError {
+            func: "char_code".to_string(),
+            message: format!("expected a single character, found \"{}\"", string),
+        }),
+    }
+}
+
+/// Character of a Unicode scalar value
+pub fn char_from_code(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let ints = exprs_into_i64(args)?;
+    u32::try_from(ints[0])
+        .ok()
+        .and_then(char::from_u32)
+        .map(Expr::Char)
+        .ok_or_else(|| InterpError::ValueError {
+            func: "char_from_code".to_string(),
+            message: format!("{} is not a valid character code", ints[0]),
+        })
+}
+
+/// Character at a character (not byte) index of a string
+/// Negative indices count back from the end, -1 being the last character
+/// First arg: string
+/// Second arg: index
+pub fn char_at(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let string: String = args[0].clone().try_into()?;
+    let idx: i64 = args[1].clone().try_into()?;
+
+    let len = string.chars().count() as i64;
+    let position = if idx < 0 { len + idx } else { idx };
+    if !(0..len).contains(&position) {
+        return Err(InterpError::ValueError {
+            func: "char_at".to_string(),
+            message: format!(
+                "index {} is out of range for a string of {} characters",
+                idx, len
+            ),
+        });
+    }
+
+    let c = string
+        .chars()
+        .nth(position as usize)
+        .expect("Index was checked to be in range");
+    Ok(Expr::Char(c))
+}
+
+/// Pads the start of a string with a character up to a width in characters
+/// First arg: string
+/// Second arg: width
+/// Third arg: single character to pad with
+pub fn pad_left(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (string, padding) = pad("pad_left", args)?;
+    Ok(Expr::String(padding + &string))
+}
+
+/// Pads the end of a string with a character up to a width in characters
+/// First arg: string
+/// Second arg: width
+/// Third arg: single character to pad with
+pub fn pad_right(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (string, padding) = pad("pad_right", args)?;
+    Ok(Expr::String(string + &padding))
+}
+
+/// Splits the arguments of pad_left and pad_right, returning the string and the padding it needs
+/// A string already at or over the width needs no padding
+fn pad(func: &str, args: &[Expr]) -> Result<(String, String), InterpError> {
+    let [string, width, fill] = args else {
+        return Err(InterpError::ArgumentError {
+            func: func.to_string(),
+            expected: Arity::Exact(3),
+            got: args.len(),
+        });
+    };
+    let string: String = string.clone().try_into()?;
+    let width: i64 = width.clone().try_into()?;
+    let fill: String = fill.clone().try_into()?;
+
+    let mut chars = fill.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return Err(InterpError::ValueError {
+            func: func.to_string(),
+            message: format!("expected a single pad character, found \"{}\"", fill),
+        });
+    };
+    let missing = usize::try_from(width)
+        .unwrap_or(0)
+        .saturating_sub(string.chars().count());
+    match missing.checked_mul(c.len_utf8()) {
+        Some(bytes) if bytes <= MAX_PADDING => Ok((string, c.to_string().repeat(missing))),
+        _ => Err(InterpError::ValueError {
+            func: func.to_string(),
+            message: format!(
+                "width {} needs more than {} bytes of padding",
+                width, MAX_PADDING
+            ),
+        }),
+    }
+}
+
+/// Most bytes of padding pad_left and pad_right add, so a huge width errors rather than allocating without limit
+const MAX_PADDING: usize = 1 << 24;
+
+/// List of the characters of a string, each as a string of one character
+pub fn string_to_list(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let string: String = single_arg("string_to_list", args)?.clone().try_into()?;
+    Ok(Expr::List(Rc::new(
+        string
+            .chars()
+            .map(|c| Expr::String(c.to_string()))
+            .collect(),
+    )))
+}
+
+/// Concatenates a list of single characters, given as strings or chars, into a string
+pub fn list_to_string(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("list_to_string", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    let mut string = String::new();
+    for element in list.iter() {
+        let element: String = element.clone().try_into()?;
+        let mut chars = element.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return Err(InterpError::ValueError {
+                func: "list_to_string".to_string(),
+                message: format!("expected a single character, found \"{}\"", element),
+            });
+        };
+        string.push(c);
+    }
+    Ok(Expr::String(string))
+}
+
+/// Splits a string into its lines, on "\n" or "\r\n" (a lone "\r" is kept in the line)
+/// A trailing newline ends the last line rather than starting an empty one, unless the second argument is true
+pub fn split_lines(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let string: String = args[0].clone().try_into()?;
+    let keep_trailing: bool = match args.get(1) {
+        Some(arg) => arg.clone().try_into()?,
+        None => false,
+    };
+
+    let mut lines: Vec<Expr> = string
+        .split('\n')
+        .map(|line| Expr::String(line.strip_suffix('\r').unwrap_or(line).to_string()))
+        .collect();
+    if !keep_trailing && lines.last() == Some(&Expr::String(String::new())) {
+        lines.pop();
+    }
+    Ok(Expr::List(Rc::new(lines)))
+}
+
+/// Checks if a string contains a character
+/// First argument is the character to check if the rest of the arguments contain
+pub fn contains(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let exprs = args
+        .iter()
+        .map(|f| f.clone().try_into())
+        .collect::<Result<Vec<String>, InterpError>>()?;
+
+    match exprs.split_first() {
+        Some((first_arg, rest)) if !rest.is_empty() => Ok(Expr::Boolean(
+            !rest.iter().any(|ele| !ele.contains(first_arg)),
+        )),
+        _ => Err(InterpError::ArgumentError {
+            func: "contains".to_string(),
+            expected: Arity::Exact(2),
+            got: exprs.len(),
+        }),
+    }
+}
+
+// Returns all the arguments as a list expression
+pub fn as_list(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(Expr::List(Rc::new(args.to_vec())))
+}
+
+// Returns the expression at the provided index of a list
+// First arg: list expr
+// Second arg: idx
+pub fn get(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let idx: &i64 = &args[1].clone().try_into()?;
+    if let Expr::List(list) = &args[0] {
+        usize::try_from(*idx)
+            .ok()
+            .and_then(|i| list.get(i))
+            .cloned()
+            .ok_or_else(|| index_error("get", *idx))
+    } else if let Expr::Pair(pair) = &args[0] {
+        // Walks the linked list up to the index
+        usize::try_from(*idx)
+            .ok()
+            .and_then(|i| pair.iter().nth(i))
+            .cloned()
+            .ok_or_else(|| index_error("get", *idx))
+    } else {
+        Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        })
     }
 }
 
 // Sets an element in a list
+// Lists are values, so this returns a new list and the original is unchanged
 // First arg: list expr
 // Second arg: idx
 // Thid arg: new element
-pub fn set(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    if args.len() != 3 {
-        return Err(InterpError::ArgumentError {
-            func: "set".to_string(),
-            expected: 3,
-            got: args.len(),
+pub fn set(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let idx: &i64 = &args[1].clone().try_into()?;
+    if let Expr::List(list) = &args[0] {
+        // Only the backing vector is copied, the elements themselves are shared
+        let mut new_list = Vec::clone(list);
+        let element = usize::try_from(*idx)
+            .ok()
+            .and_then(|i| new_list.get_mut(i))
+            .ok_or_else(|| index_error("set", *idx))?;
+        *element = args[2].clone();
+        Ok(Expr::List(Rc::new(new_list)))
+    } else {
+        Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        })
+    }
+}
+
+/// Error for an index which is negative or past the end of a list
+fn index_error(func: &str, idx: i64) -> InterpError {
+    InterpError::ValueError {
+        func: func.to_string(),
+        message: format!("index {} is out of range", idx),
+    }
+}
+
+/// Deep copy of a value, where every list (including nested ones) gets its own backing vector
+/// No builtin changes a list in place, so the copy behaves the same as the original, this only forces the duplication
+pub fn copy(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    fn deep_copy(expr: &Expr) -> Expr {
+        match expr {
+            Expr::List(list) => Expr::List(Rc::new(list.iter().map(deep_copy).collect())),
+            other => other.clone(),
+        }
+    }
+    Ok(deep_copy(single_arg("copy", args)?))
+}
+
+// Appends an element to the end of a list, returning the new list
+// First arg: list expr
+// Second arg: new element
+pub fn append(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    if let Expr::List(list) = &args[0] {
+        let mut new_list = Vec::with_capacity(list.len() + 1);
+        new_list.extend(list.iter().cloned());
+        new_list.push(args[1].clone());
+        Ok(Expr::List(Rc::new(new_list)))
+    } else {
+        Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        })
+    }
+}
+
+/// First n elements of a list, the whole list if it is shorter than n
+/// First arg: list expr
+/// Second arg: n
+pub fn take(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (list, n) = list_and_count("take", args)?;
+    Ok(Expr::List(Rc::new(list[..n].to_vec())))
+}
+
+/// All but the first n elements of a list, an empty list if it is shorter than n
+/// First arg: list expr
+/// Second arg: n
+pub fn drop(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (list, n) = list_and_count("drop", args)?;
+    Ok(Expr::List(Rc::new(list[n..].to_vec())))
+}
+
+/// Splits the arguments of take and drop, clamping the count to the length of the list
+fn list_and_count<'a>(func: &str, args: &'a [Expr]) -> Result<(&'a [Expr], usize), InterpError> {
+    let Expr::List(list) = &args[0] else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    let n: i64 = args[1].clone().try_into()?;
+    if n < 0 {
+        return Err(InterpError::ValueError {
+            func: func.to_string(),
+            message: format!("count must not be negative, found {}", n),
         });
     }
 
-    let idx: &i64 = &args[1].clone().try_into()?;
+    Ok((list, (n as usize).min(list.len())))
+}
+
+/// First element of a list for which the predicate is true, or nil if there is none
+/// First arg: list expr
+/// Second arg: predicate function
+pub fn find(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (list, predicate) = list_and_predicate("find", args)?;
+    for element in list.iter() {
+        if test_predicate(predicate, element, interpreter)? {
+            return Ok(element.clone());
+        }
+    }
+    Ok(Expr::Nil)
+}
+
+/// Whether the predicate is true for any element of a list, false for an empty list
+/// First arg: list expr
+/// Second arg: predicate function
+pub fn any(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (list, predicate) = list_and_predicate("any?", args)?;
+    for element in list.iter() {
+        if test_predicate(predicate, element, interpreter)? {
+            return Ok(Expr::Boolean(true));
+        }
+    }
+    Ok(Expr::Boolean(false))
+}
+
+/// Whether the predicate is true for every element of a list, true for an empty list
+/// First arg: list expr
+/// Second arg: predicate function
+pub fn all(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (list, predicate) = list_and_predicate("all?", args)?;
+    for element in list.iter() {
+        if !test_predicate(predicate, element, interpreter)? {
+            return Ok(Expr::Boolean(false));
+        }
+    }
+    Ok(Expr::Boolean(true))
+}
+
+/// Splits the arguments of functions taking a list and a predicate function
+fn list_and_predicate<'a>(
+    func: &str,
+    args: &'a [Expr],
+) -> Result<(&'a [Expr], &'a Function), InterpError> {
+    match args {
+        [Expr::List(list), Expr::Function(predicate)] => Ok((list, predicate)),
+        [Expr::List(_), other] => Err(InterpError::TypeError {
+            expected: "function".to_string(),
+            found: other.to_string(),
+        }),
+        [other, _] => Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: other.to_string(),
+        }),
+        _ => Err(InterpError::ArgumentError {
+            func: func.to_string(),
+            expected: Arity::Exact(2),
+            got: args.len(),
+        }),
+    }
+}
+
+/// Calls a predicate with one element, which must give a boolean
+fn test_predicate(
+    predicate: &Function,
+    element: &Expr,
+    interpreter: &mut Interpreter,
+) -> Result<bool, InterpError> {
+    apply_function(predicate, std::slice::from_ref(element), interpreter)?.try_into()
+}
+
+/// Counts the elements of a list equal to the target
+/// First arg: list expr
+/// Second arg: target
+pub fn count(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     if let Expr::List(list) = &args[0] {
-        let mut new_list = list.clone();
-        new_list[*idx as usize] = args[2].clone();
-        Ok(Expr::List(new_list))
+        let count = list.iter().filter(|ele| **ele == args[1]).count();
+        Ok(Expr::Integer(count as i64))
     } else {
         Err(InterpError::TypeError {
             expected: "list".to_string(),
@@ -461,31 +1373,1624 @@ pub fn set(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError
 }
 
 /// Gives the length of a string or a list
-pub fn length(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
+pub fn length(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
     match &args[0] {
         Expr::String(str) => Ok(Expr::Integer(str.len() as i64)),
         Expr::List(list) => Ok(Expr::Integer(list.len() as i64)),
+        // Counted by walking the linked list
+        Expr::Pair(pair) => Ok(Expr::Integer(pair.iter().count() as i64)),
         _ => Err(InterpError::TypeError {
-            expected: "string or list".to_string(),
+            expected: "string, list, or pair".to_string(),
             found: args[0].to_string(),
         }),
     }
 }
 
-/// Sorts a list, returning new sorted list
-pub fn sort(args: &[Expr], _global: &mut Environment) -> Result<Expr, InterpError> {
-    if let Expr::List(list) = &args[0] {
-        // Make sure integers
-        let mut int_list = exprs_into_i64(list.as_slice())?;
-        int_list.sort();
+/// Sorts a list of numbers, strings, characters, or lists (ordered as by less?), returning new sorted list
+/// The sort is stable, equal elements (such as 1 and 1.0) keep their order from the original list
+pub fn sort(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("sort", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
 
-        Ok(Expr::List(
-            int_list.into_iter().map(|i| Expr::Integer(i)).collect(),
-        ))
+    // Comparing can only fail between elements of different types or with NaN, which is checked first
+    // A comparison failing while sorting would leave the order inconsistent, which the sort may panic on
+    check_sortable(list)?;
+    let mut sorted = list.to_vec();
+    sorted.sort_by(|a, b| {
+        compare(a, b)
+            .ok()
+            .flatten()
+            .expect("Elements were checked to be comparable")
+    });
+    Ok(Expr::List(Rc::new(sorted)))
+}
+
+/// Type of value sort orders, every element of a sorted list must have the same one
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKind {
+    Number,
+    String,
+    Char,
+    List,
+}
+
+/// Checks that every two of the values can be compared, so sorting them is a total order
+/// Lists are compared element by element, so the elements at each index of all the lists are checked together
+fn check_sortable(values: &[Expr]) -> Result<(), InterpError> {
+    let mut pending: Vec<Vec<&Expr>> = vec![values.iter().collect()];
+    while let Some(group) = pending.pop() {
+        let mut first: Option<(&Expr, SortKind)> = None;
+        for value in &group {
+            let kind = match value {
+                Expr::Float(float) if float.is_nan() => {
+                    return Err(InterpError::TypeError {
+                        expected: "a number that can be ordered".to_string(),
+                        found: value.repr(),
+                    })
+                }
+                Expr::Integer(_) | Expr::Float(_) => SortKind::Number,
+                Expr::String(_) => SortKind::String,
+                Expr::Char(_) => SortKind::Char,
+                Expr::List(_) => SortKind::List,
+                _ => {
+                    return Err(InterpError::TypeError {
+                        expected: "numbers, strings, chars, or lists".to_string(),
+                        found: value.repr(),
+                    })
+                }
+            };
+            match first {
+                None => first = Some((value, kind)),
+                Some((other, other_kind)) if other_kind != kind => {
+                    return Err(InterpError::TypeError {
+                        expected: "elements of one type".to_string(),
+                        found: format!("{} and {}", other.repr(), value.repr()),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some((_, SortKind::List)) = first {
+            let lists: Vec<&[Expr]> = group
+                .iter()
+                .filter_map(|value| match value {
+                    Expr::List(list) => Some(list.as_slice()),
+                    _ => None,
+                })
+                .collect();
+            let longest = lists.iter().map(|list| list.len()).max().unwrap_or(0);
+            for i in 0..longest {
+                pending.push(lists.iter().filter_map(|list| list.get(i)).collect());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pairs each element of a list with its index, as a list of [index, element] lists
+pub fn enumerate(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("enumerate", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    let pairs = list
+        .iter()
+        .enumerate()
+        .map(|(i, element)| Expr::List(Rc::new(vec![Expr::Integer(i as i64), element.clone()])))
+        .collect();
+    Ok(Expr::List(Rc::new(pairs)))
+}
+
+/// Sum of a list of integers, 0 for an empty list
+pub fn sum(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("sum", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    exprs_into_i64(list)?
+        .into_iter()
+        .try_fold(0, i64::checked_add)
+        .map(Expr::Integer)
+        .ok_or_else(|| overflow_error("sum"))
+}
+
+/// Product of a list of integers, 1 for an empty list
+pub fn product(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("product", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    exprs_into_i64(list)?
+        .into_iter()
+        .try_fold(1, i64::checked_mul)
+        .map(Expr::Integer)
+        .ok_or_else(|| overflow_error("product"))
+}
+
+/// Pair of an element in front of a linked list, which is another pair or nil for an empty one
+/// Shares the rest rather than copying it, unlike adding to a list
+pub fn cons(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let [head, rest] = args else {
+        return Err(InterpError::ArgumentError {
+            func: "cons".to_string(),
+            expected: Arity::Exact(2),
+            got: args.len(),
+        });
+    };
+    match rest {
+        Expr::Pair(_) | Expr::Nil => Ok(Expr::Pair(Rc::new(Pair {
+            head: head.clone(),
+            rest: rest.clone(),
+        }))),
+        _ => Err(InterpError::TypeError {
+            expected: "pair or nil".to_string(),
+            found: rest.to_string(),
+        }),
+    }
+}
+
+/// First element of a pair or a non-empty list
+pub fn head(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("head", args)? {
+        Expr::Pair(pair) => Ok(pair.head.clone()),
+        Expr::List(list) => list
+            .first()
+            .cloned()
+            .ok_or_else(|| empty_list_error("head")),
+        arg => Err(InterpError::TypeError {
+            expected: "pair or list".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Elements after the first of a pair or a non-empty list
+/// The rest of a pair is shared, while the rest of a list is a copy of its other elements
+pub fn tail(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("tail", args)? {
+        Expr::Pair(pair) => Ok(pair.rest.clone()),
+        Expr::List(list) => match list.split_first() {
+            Some((_, rest)) => Ok(Expr::List(Rc::new(rest.to_vec()))),
+            None => Err(empty_list_error("tail")),
+        },
+        arg => Err(InterpError::TypeError {
+            expected: "pair or list".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+fn empty_list_error(func: &str) -> InterpError {
+    InterpError::ValueError {
+        func: func.to_string(),
+        message: "the list is empty".to_string(),
+    }
+}
+
+/// Linked list of pairs with the elements of a list, nil for an empty list
+pub fn list_to_pairs(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("list_to_pairs", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    Ok(list.iter().rev().fold(Expr::Nil, |rest, head| {
+        Expr::Pair(Rc::new(Pair {
+            head: head.clone(),
+            rest,
+        }))
+    }))
+}
+
+/// List of the elements of a linked list of pairs, nil is the empty list
+pub fn pairs_to_list(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("pairs_to_list", args)? {
+        Expr::Pair(pair) => Ok(Expr::List(Rc::new(pair.iter().cloned().collect()))),
+        Expr::Nil => Ok(Expr::List(Rc::new(vec![]))),
+        arg => Err(InterpError::TypeError {
+            expected: "pair or nil".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Copy of a list without duplicate elements (as compared by equal?), keeping the first of each in order
+pub fn unique(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let Expr::List(list) = single_arg("unique", args)? else {
+        return Err(InterpError::TypeError {
+            expected: "list".to_string(),
+            found: args[0].to_string(),
+        });
+    };
+    // Values cannot be hashed (1 equals 1.0), so each element is compared with those kept so far
+    let mut kept: Vec<Expr> = Vec::with_capacity(list.len());
+    for element in list.iter() {
+        if !kept.contains(element) {
+            kept.push(element.clone());
+        }
+    }
+    Ok(Expr::List(Rc::new(kept)))
+}
+
+/// Gives the number of arguments a function takes, or nil for builtins taking a varying number of them
+pub fn arity(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    if let Expr::Function(func) = &args[0] {
+        Ok(func
+            .arity()
+            .map_or(Expr::Nil, |arity| Expr::Integer(arity as i64)))
     } else {
         Err(InterpError::TypeError {
-            expected: "integer list".to_string(),
+            expected: "function".to_string(),
             found: args[0].to_string(),
         })
     }
 }
+
+/// Gives the doc string of a function, or nil for user functions without one
+pub fn doc(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match single_arg("doc", args)? {
+        Expr::Function(Function::CoreFunction { doc, .. }) => Ok(Expr::String(doc.to_string())),
+        Expr::Function(Function::Function { doc, .. }) => Ok(doc
+            .as_ref()
+            .map_or(Expr::Nil, |doc| Expr::String(doc.to_string()))),
+        arg => Err(InterpError::TypeError {
+            expected: "function".to_string(),
+            found: arg.to_string(),
+        }),
+    }
+}
+
+/// Composes two functions, returning a function of one argument which applies the second and then the first
+/// First arg: outer function (f)
+/// Second arg: inner function (g)
+pub fn compose(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    if let Some(arg) = args.iter().find(|arg| !matches!(arg, Expr::Function(_))) {
+        return Err(InterpError::TypeError {
+            expected: "function".to_string(),
+            found: arg.to_string(),
+        });
+    }
+
+    // Equivalent to lambda(x) { f(g(x)) }, with f and g held directly so they do not depend on any scope
+    let param = "x".to_string();
+    let block = Rc::new(vec![Ast::Application(vec![
+        Ast::Value(args[0].clone()),
+        Ast::Application(vec![
+            Ast::Value(args[1].clone()),
+            Ast::Identifier(param.clone()),
+        ]),
+    ])]);
+
+    Ok(create_function(
+        &Rc::new(vec![param]),
+        &Rc::new(vec![]),
+        &block,
+        None,
+        Some("compose"),
+        interpreter,
+    ))
+}
+
+/// Returns its argument unchanged
+pub fn identity(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(single_arg("identity", args)?.clone())
+}
+
+/// Returns a function of no arguments which always returns the given value
+pub fn constantly(args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let value = single_arg("constantly", args)?;
+
+    // Equivalent to lambda() { value }, with the value held directly so it does not depend on any scope
+    let block = Rc::new(vec![Ast::Value(value.clone())]);
+
+    Ok(create_function(
+        &Rc::new(vec![]),
+        &Rc::new(vec![]),
+        &block,
+        None,
+        Some("constantly"),
+        interpreter,
+    ))
+}
+
+// Results of fallible operations are tagged two element lists, ["Ok", value] or ["Err", error]
+const OK_TAG: &str = "Ok";
+const ERR_TAG: &str = "Err";
+
+/// Successful result holding the argument
+pub fn ok(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(tagged(OK_TAG, single_arg("ok", args)?))
+}
+
+/// Failed result holding the argument, usually an error message
+pub fn err(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    Ok(tagged(ERR_TAG, single_arg("err", args)?))
+}
+
+/// Whether a result is successful
+pub fn is_ok(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let (is_ok, _) = result_parts(single_arg("is_ok?", args)?)?;
+    Ok(Expr::Boolean(is_ok))
+}
+
+/// Value of a successful result, errors on a failed one
+pub fn unwrap(args: &[Expr], _interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    match result_parts(single_arg("unwrap", args)?)? {
+        (true, value) => Ok(value.clone()),
+        (false, error) => Err(InterpError::ValueError {
+            func: "unwrap".to_string(),
+            message: format!("called on an err: {}", error.display_escaped()),
+        }),
+    }
+}
+
+fn tagged(tag: &str, value: &Expr) -> Expr {
+    Expr::List(Rc::new(vec![Expr::String(tag.to_string()), value.clone()]))
+}
+
+/// Whether a result is successful, and the value it holds
+fn result_parts(result: &Expr) -> Result<(bool, &Expr), InterpError> {
+    if let Expr::List(list) = result {
+        if let [Expr::String(tag), value] = list.as_slice() {
+            match tag.as_str() {
+                OK_TAG => return Ok((true, value)),
+                ERR_TAG => return Ok((false, value)),
+                _ => {}
+            }
+        }
+    }
+    Err(InterpError::TypeError {
+        expected: "result (from ok or err)".to_string(),
+        found: result.to_string(),
+    })
+}
+
+/// Lists the names of every binding in scope, innermost first
+pub fn env_names(_args: &[Expr], interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+    let names = interpreter.local.borrow().names();
+    Ok(Expr::List(Rc::new(
+        names.into_iter().map(Expr::String).collect(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        environment::{Environment, OutputHook},
+        interpret_default,
+    };
+
+    fn run(input: &str) -> Result<Expr, InterpError> {
+        let ast = parser::parse("test", input).expect("Test program should parse");
+        interpret_default(ast, true, true).map(|(expr, _)| expr)
+    }
+
+    fn int_list(ints: &[i64]) -> Expr {
+        Expr::List(Rc::new(ints.iter().map(|i| Expr::Integer(*i)).collect()))
+    }
+
+    #[test]
+    fn set_does_not_mutate_original() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let original = int_list(&[1, 2, 3]);
+
+        let new_list = set(
+            &[original.clone(), Expr::Integer(0), Expr::Integer(10)],
+            &mut env,
+        )?;
+        assert_eq!(new_list, int_list(&[10, 2, 3]));
+        assert_eq!(original, int_list(&[1, 2, 3]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_out_of_range() -> Result<(), InterpError> {
+        assert_eq!(run("get([1, 2], 1)")?, Expr::Integer(2));
+        assert_eq!(run("set([1, 2], 1, 3)")?, int_list(&[1, 3]));
+
+        // Negative indices and ones past the end are errors for lists and linked lists alike
+        for source in [
+            "get([1], 5)",
+            "get([1], -1)",
+            "get([], 0)",
+            "set([1], 1, 2)",
+            "set([1], -1, 2)",
+            "get(cons(1, nil), 1)",
+            "get(cons(1, nil), -1)",
+        ] {
+            assert!(
+                run(source).is_err_and(|e| matches!(e, InterpError::ValueError { .. })),
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            run("get([1], -1)").unwrap_err().to_string(),
+            run("get(cons(1, nil), -1)").unwrap_err().to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn append_does_not_mutate_original() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let original = int_list(&[1, 2]);
+
+        let new_list = append(&[original.clone(), Expr::Integer(3)], &mut env)?;
+        assert_eq!(new_list, int_list(&[1, 2, 3]));
+        assert_eq!(original, int_list(&[1, 2]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_bindings_are_independent() -> Result<(), InterpError> {
+        let result = run(r#"{
+            let a = as_list(1, 2, 3);
+            let b = a;
+            b = set(b, 0, 10);
+            let c = append(a, 4);
+            as_list(a, b, c)
+        }"#)?;
+        assert_eq!(result.to_string(), "[[1, 2, 3], [10, 2, 3], [1, 2, 3, 4]]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn gcd_and_lcm() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ints = |a: i64, b: i64| [Expr::Integer(a), Expr::Integer(b)];
+
+        assert_eq!(gcd(&ints(12, 18), &mut env)?, Expr::Integer(6));
+        assert_eq!(gcd(&ints(-12, 18), &mut env)?, Expr::Integer(6));
+        assert_eq!(gcd(&ints(0, 0), &mut env)?, Expr::Integer(0));
+        assert_eq!(lcm(&ints(4, 6), &mut env)?, Expr::Integer(12));
+        assert_eq!(lcm(&ints(0, 6), &mut env)?, Expr::Integer(0));
+        assert!(lcm(&ints(i64::MAX, i64::MAX - 1), &mut env)
+            .is_err_and(|e| matches!(e, InterpError::ArithmeticError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn enumerate_pairs() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"enumerate(["a", "b", "c"])"#)?.to_string(),
+            r#"[[0, "a"], [1, "b"], [2, "c"]]"#
+        );
+        assert_eq!(run("enumerate([])")?.to_string(), "[]");
+        assert!(run(r#"enumerate("abc")"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_sum_and_product() -> Result<(), InterpError> {
+        assert_eq!(run("sum(as_list(1, 2, 3))")?, Expr::Integer(6));
+        assert_eq!(run("product([2, 3, 4])")?, Expr::Integer(24));
+        assert_eq!(run("sum([])")?, Expr::Integer(0));
+        assert_eq!(run("product([])")?, Expr::Integer(1));
+
+        assert!(run(r#"sum([1, "2"])"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        assert!(run("product(2)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        assert!(run("sum([9223372036854775807, 1])")
+            .is_err_and(|e| matches!(e, InterpError::ArithmeticError { .. })));
+        assert!(run("product([4294967296, 4294967296])")
+            .is_err_and(|e| matches!(e, InterpError::ArithmeticError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn linked_lists() -> Result<(), InterpError> {
+        assert_eq!(run("cons(1, cons(2, nil))")?.to_string(), "(1, 2)");
+        assert_eq!(
+            run(r#"list_to_pairs([1, "a", [2]])"#)?.to_string(),
+            r#"(1, "a", [2])"#
+        );
+        assert_eq!(run("list_to_pairs([])")?, Expr::Nil);
+        assert_eq!(
+            run("pairs_to_list(list_to_pairs([1, 2, 3]))")?.to_string(),
+            "[1, 2, 3]"
+        );
+        assert_eq!(run("pairs_to_list(nil)")?.to_string(), "[]");
+
+        // head and tail work on both representations, length and get walk the pairs
+        let pairs = "list_to_pairs([1, 2, 3])";
+        assert_eq!(run(&format!("head({pairs})"))?, Expr::Integer(1));
+        assert_eq!(run(&format!("tail({pairs})"))?.to_string(), "(2, 3)");
+        assert_eq!(run("head([1, 2, 3])")?, Expr::Integer(1));
+        assert_eq!(run("tail([1, 2, 3])")?.to_string(), "[2, 3]");
+        assert_eq!(run(&format!("length({pairs})"))?, Expr::Integer(3));
+        assert_eq!(run(&format!("get({pairs}, 2)"))?, Expr::Integer(3));
+        assert!(run(&format!("get({pairs}, 3)"))
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        assert_eq!(
+            run("equal?(cons(1, nil), list_to_pairs([1]))")?,
+            Expr::Boolean(true)
+        );
+        assert_eq!(run("equal?(cons(1, nil), [1])")?, Expr::Boolean(false));
+
+        assert!(run("cons(1, [2])").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        assert!(run("head([])").is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(run("tail(nil)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn long_linked_list() -> Result<(), InterpError> {
+        // Built and dropped without recursion
+        let list = Expr::List(Rc::new((0..100_000).map(Expr::Integer).collect()));
+        let mut env = Interpreter::new(true, false, None);
+        let pairs = list_to_pairs(&[list], &mut env)?;
+        assert_eq!(
+            length(std::slice::from_ref(&pairs), &mut env)?,
+            Expr::Integer(100_000)
+        );
+        std::mem::drop(pairs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unique_elements() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"unique([3, "a", 1, 3, [1], "a", 2, [1], 1])"#)?.to_string(),
+            r#"[3, "a", 1, [1], 2]"#
+        );
+        assert_eq!(run("unique([1, 2, 3])")?.to_string(), "[1, 2, 3]");
+        // The first of equal elements is kept
+        assert_eq!(run("unique([1, to_float(1)])")?.to_string(), "[1]");
+        assert_eq!(run("unique([])")?.to_string(), "[]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_mixed_types() {
+        // A comparison failing midway used to leave the sort with an inconsistent order, which panicked
+        let mut rng = crate::random::Rng::from_seed(1153);
+        let mut elements: Vec<Expr> = (0..200)
+            .map(|_| Expr::Integer(rng.next_u64() as i64 % 1000))
+            .collect();
+        elements.insert(137, Expr::String("a".to_string()));
+        let mut env = Interpreter::new(true, false, None);
+        assert!(sort(&[Expr::List(Rc::new(elements))], &mut env)
+            .is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        // Integers and floats are ordered exactly, even where an integer does not fit in f64
+        let big = 1i64 << 53;
+        let mixed = vec![
+            Expr::Integer(big + 1),
+            Expr::Float(big as f64),
+            Expr::Integer(big),
+            Expr::Float(2.5),
+            Expr::Integer(2),
+        ];
+        assert_eq!(
+            sort(&[Expr::List(Rc::new(mixed))], &mut env)
+                .unwrap()
+                .to_string(),
+            format!("[2, 2.5, {}.0, {}, {}]", big, big, big + 1)
+        );
+    }
+
+    #[test]
+    fn stable_sort() -> Result<(), InterpError> {
+        assert_eq!(run("sort([3, -1, 2, 0])")?.to_string(), "[-1, 0, 2, 3]");
+        assert_eq!(
+            run(r#"sort(["b", "a", "ab"])"#)?.to_string(),
+            r#"["a", "ab", "b"]"#
+        );
+
+        // 1 and 1.0 are equal, so they stay in the order they were given
+        let duplicates = run("sort([2, to_float(1), 1, 0, to_float(2), 1])")?;
+        let Expr::List(sorted) = &duplicates else {
+            panic!("sort should return a list");
+        };
+        let kinds: Vec<&str> = sorted
+            .iter()
+            .map(|expr| match expr {
+                Expr::Float(_) => "float",
+                _ => "integer",
+            })
+            .collect();
+        assert_eq!(duplicates.to_string(), "[0, 1.0, 1, 1, 2, 2.0]");
+        assert_eq!(
+            kinds,
+            vec!["integer", "float", "integer", "integer", "integer", "float"]
+        );
+
+        assert!(run(r#"sort([1, "a"])"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        assert!(run(r#"sort([[1, 2], [1, "a"]])"#)
+            .is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        // Lists compare by index, so different types at different indexes are fine
+        assert_eq!(
+            run(r#"sort([[2, "b"], [1, "a"], [1]])"#)?.to_string(),
+            r#"[[1], [1, "a"], [2, "b"]]"#
+        );
+        assert!(run("sort(1)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trip() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"to_json(as_list(1, "a", as_list(true, as_list())))"#)?,
+            Expr::String(r#"[1,"a",[true,[]]]"#.to_string())
+        );
+        assert_eq!(
+            run(
+                r#"{ let data = [1, ["two", [3]], false]; equal?(from_json(to_json(data)), data) }"#
+            )?,
+            Expr::Boolean(true)
+        );
+        assert_eq!(
+            run(r#"from_json("[null, 1.5]")"#)?,
+            Expr::List(Rc::new(vec![Expr::Nil, Expr::Float(1.5)]))
+        );
+        // Objects have no value that converts back to them, so they are an error rather than changing shape
+        for json in [r#"{"a": 1}"#, "[1, {}]"] {
+            assert!(run(&format!("from_json({:?})", json))
+                .is_err_and(|e| e.to_string().contains("objects are not supported")));
+        }
+
+        assert!(run(r#"from_json("[1, 2")"#)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(
+            run("to_json(as_list(add))").is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_template() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"format("{} + {} = {}", 1, 2, 3)"#)?,
+            Expr::String("1 + 2 = 3".to_string())
+        );
+        assert_eq!(
+            run(r#"format("{{{}}} in {}", "x", as_list(1, 2))"#)?,
+            Expr::String("{x} in [1, 2]".to_string())
+        );
+        assert_eq!(run(r#"format("none")"#)?, Expr::String("none".to_string()));
+
+        assert!(run(r#"format("{} and {}", 1)"#).is_err_and(|e| {
+            e.to_string()
+                == "Invalid value supplied to function 'format': template has 2 placeholder(s) but 1 value(s) were given"
+        }));
+        assert!(run(r#"format("{}", 1, 2)"#)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(
+            run(r#"format("{x}", 1)"#).is_err_and(|e| matches!(e, InterpError::ValueError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_arity_checked() {
+        // length does no checking of its own, the arity it was registered with is checked for it
+        for (input, func, expected, got) in [
+            ("div(1)", "div", Arity::Exact(2), 1),
+            ("length()", "length", Arity::Exact(1), 0),
+            (
+                "string_replace(\"a\", \"b\", \"c\", true, 1)",
+                "string_replace",
+                Arity::Between(3, 4),
+                5,
+            ),
+            ("contains(\"a\")", "contains", Arity::AtLeast(2), 1),
+            ("to_uppercase()", "to_uppercase", Arity::AtLeast(1), 0),
+        ] {
+            let error = InterpError::ArgumentError {
+                func: func.to_string(),
+                expected,
+                got,
+            };
+            assert_eq!(run(input).unwrap_err().to_string(), error.to_string());
+        }
+        assert!(run("string_replace(\"a\", \"b\", \"c\", true, 1)")
+            .is_err_and(|e| e.to_string().ends_with("expected 3 to 4, got 5")));
+    }
+
+    #[test]
+    fn padding() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"pad_left("7", 3, "0")"#)?,
+            Expr::String("007".to_string())
+        );
+        assert_eq!(
+            run(r#"pad_right("ab", 4, '.')"#)?,
+            Expr::String("ab..".to_string())
+        );
+        // Widths count characters, not bytes
+        assert_eq!(
+            run(r#"pad_left("λ", 2, "é")"#)?,
+            Expr::String("éλ".to_string())
+        );
+        // Strings at or over the width are unchanged
+        assert_eq!(
+            run(r#"pad_right("long", 2, " ")"#)?,
+            Expr::String("long".to_string())
+        );
+        assert_eq!(
+            run(r#"pad_left("x", -1, " ")"#)?,
+            Expr::String("x".to_string())
+        );
+
+        for fill in [r#""""#, r#""ab""#] {
+            assert!(run(&format!(r#"pad_left("x", 3, {})"#, fill))
+                .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        }
+
+        // Huge widths are an error rather than an allocation that fails or overflows
+        for width in ["9223372036854775807", "16777217"] {
+            assert!(run(&format!(r#"pad_left("x", {}, "é")"#, width))
+                .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        }
+        assert!(run(r#"pad_right("x", 9223372036854775807, " ")"#)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn string_list_round_trip() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"string_to_list("aλé")"#)?.to_string(),
+            r#"["a", "λ", "é"]"#
+        );
+        assert_eq!(
+            run(r#"list_to_string(string_to_list("héllo, wörld"))"#)?,
+            Expr::String("héllo, wörld".to_string())
+        );
+        assert_eq!(run(r#"string_to_list("")"#)?.to_string(), "[]");
+        assert_eq!(
+            run(r#"list_to_string(['o', "k"])"#)?,
+            Expr::String("ok".to_string())
+        );
+
+        assert!(run(r#"list_to_string(["ab"])"#)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(
+            run("list_to_string([1])").is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_lines_endings() -> Result<(), InterpError> {
+        let lines = |strings: &[&str]| {
+            Expr::List(Rc::new(
+                strings
+                    .iter()
+                    .map(|s| Expr::String(s.to_string()))
+                    .collect(),
+            ))
+        };
+        assert_eq!(run(r#"split_lines("a\nb")"#)?, lines(&["a", "b"]));
+        assert_eq!(run(r#"split_lines("a\r\nb\r\n")"#)?, lines(&["a", "b"]));
+        // A lone carriage return does not end a line
+        assert_eq!(run(r#"split_lines("a\rb\n")"#)?, lines(&["a\rb"]));
+
+        // The trailing newline only gives an empty last line when asked for
+        assert_eq!(run(r#"split_lines("a\n\n")"#)?, lines(&["a", ""]));
+        assert_eq!(run(r#"split_lines("a\n", true)"#)?, lines(&["a", ""]));
+        assert_eq!(run(r#"split_lines("")"#)?, lines(&[]));
+        assert!(run("split_lines(1)").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_conditions() -> Result<(), InterpError> {
+        assert_eq!(run("not(false)")?, Expr::Boolean(true));
+        assert_eq!(run("and(true, less?(1, 2))")?, Expr::Boolean(true));
+        assert_eq!(run("and(true, false)")?, Expr::Boolean(false));
+        assert_eq!(run("or(false, true)")?, Expr::Boolean(true));
+        assert_eq!(run("or()")?, Expr::Boolean(false));
+        assert_eq!(run("and()")?, Expr::Boolean(true));
+
+        // Only booleans are conditions, there are no truthy or falsy values
+        for value in ["0", "1", r#""""#, "[]", "as_list(1)"] {
+            for program in [
+                format!("not({})", value),
+                format!("and(true, {})", value),
+                format!("or(false, {})", value),
+                format!("cond ({} => 1) (true => 2)", value),
+                format!("any?(as_list(1), lambda(x) {{ {} }})", value),
+            ] {
+                assert!(
+                    run(&program).is_err_and(|e| matches!(e, InterpError::TypeError { .. })),
+                    "{} should be a type error",
+                    program
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_overflow() -> Result<(), InterpError> {
+        assert_eq!(run("add(9223372036854775806, 1)")?, Expr::Integer(i64::MAX));
+        assert_eq!(run("sub()")?, Expr::Integer(0));
+        assert_eq!(run("sub(5)")?, Expr::Integer(5));
+        assert_eq!(run("sub(10, 3, 2)")?, Expr::Integer(5));
+
+        for program in [
+            "add(9223372036854775807, 1)",
+            "add(-9223372036854775808, -1)",
+            "sub(-9223372036854775808, 1)",
+            "sub(0, -9223372036854775808)",
+            "mul(4294967296, 4294967296)",
+            "mul(-9223372036854775808, -1)",
+        ] {
+            assert!(
+                run(program).is_err_and(|e| e.to_string()
+                    == format!(
+                        "Arithmetic error in function '{}': result overflowed",
+                        &program[..3]
+                    )),
+                "{} should overflow",
+                program
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn euclidean_modulo() -> Result<(), InterpError> {
+        assert_eq!(run("rem(-1, 3)")?, Expr::Integer(-1));
+        assert_eq!(run("mod(-1, 3)")?, Expr::Integer(2));
+        assert_eq!(run("mod(7, 3)")?, Expr::Integer(1));
+        // Never negative, even for a negative divisor
+        assert_eq!(run("mod(-7, -3)")?, Expr::Integer(2));
+        assert_eq!(run("mod(-9223372036854775808, -1)")?, Expr::Integer(0));
+
+        assert!(run("mod(1, 0)").is_err_and(|e| {
+            e.to_string() == "Arithmetic error in function 'mod': division by zero"
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bitwise() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ints = |a: i64, b: i64| [Expr::Integer(a), Expr::Integer(b)];
+
+        assert_eq!(
+            bitand(&ints(0b1100, 0b1010), &mut env)?,
+            Expr::Integer(0b1000)
+        );
+        assert_eq!(
+            bitor(&ints(0b1100, 0b1010), &mut env)?,
+            Expr::Integer(0b1110)
+        );
+        assert_eq!(
+            bitxor(&ints(0b1100, 0b1010), &mut env)?,
+            Expr::Integer(0b0110)
+        );
+        assert_eq!(shl(&ints(1, 10), &mut env)?, Expr::Integer(1024));
+        assert_eq!(shl(&ints(1, 63), &mut env)?, Expr::Integer(i64::MIN));
+        assert_eq!(shr(&ints(1024, 3), &mut env)?, Expr::Integer(128));
+        assert_eq!(shr(&ints(-8, 1), &mut env)?, Expr::Integer(-4));
+        for amount in [64, -1] {
+            assert!(shl(&ints(1, amount), &mut env)
+                .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        }
+        assert!(shr(&ints(1, 64), &mut env).is_err_and(|e| {
+            e.to_string() == "Invalid value supplied to function 'shr': shift amount 64 is not between 0 and 63"
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn random_is_reproducible_from_seed() -> Result<(), InterpError> {
+        let range = [Expr::Integer(0), Expr::Integer(100)];
+        let sequence = |seed: u64| {
+            let mut env = Interpreter::with_seed(true, false, None, seed);
+            (0..5)
+                .map(|_| random(&range, &mut env))
+                .collect::<Result<Vec<Expr>, InterpError>>()
+        };
+
+        let expected: Vec<Expr> = vec![22, 27, 87, 70, 15]
+            .into_iter()
+            .map(Expr::Integer)
+            .collect();
+        assert_eq!(sequence(417)?, expected);
+        assert_eq!(sequence(417)?, sequence(417)?);
+
+        let mut env = Interpreter::with_seed(true, false, None, 417);
+
+        // Empty range
+        assert!(random(&[Expr::Integer(5), Expr::Integer(5)], &mut env)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn clock_is_monotonic() -> Result<(), InterpError> {
+        let result = run(r#"{
+            let first = clock();
+            let second = clock();
+            as_list(first, second)
+        }"#)?;
+        let Expr::List(readings) = result else {
+            panic!("Expected a list of readings, found {result}");
+        };
+        let first: i64 = readings[0].clone().try_into()?;
+        let second: i64 = readings[1].clone().try_into()?;
+        assert!(first >= 0);
+        assert!(second >= first);
+
+        Ok(())
+    }
+
+    #[test]
+    fn string_replace_occurrences() -> Result<(), InterpError> {
+        assert_eq!(
+            run(r#"string_replace("a-b-c", "-", "+")"#)?,
+            Expr::String("a+b+c".to_string())
+        );
+        assert_eq!(
+            run(r#"string_replace("a-b-c", "-", "+", true)"#)?,
+            Expr::String("a+b-c".to_string())
+        );
+        // No match returns the original string
+        assert_eq!(
+            run(r#"string_replace("abc", "x", "y")"#)?,
+            Expr::String("abc".to_string())
+        );
+        assert!(run(r#"string_replace("abc", "", "y")"#)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn char_codes() -> Result<(), InterpError> {
+        assert_eq!(run(r#"char_code("A")"#)?, Expr::Integer(65));
+        assert_eq!(run(r#"char_code("🦀")"#)?, Expr::Integer(0x1F980));
+        assert_eq!(run("char_code('A')")?, Expr::Integer(65));
+        assert_eq!(run("char_from_code(65)")?, Expr::Char('A'));
+        assert!(
+            run(r#"char_code("AB")"#).is_err_and(|e| matches!(e, InterpError::ValueError { .. }))
+        );
+        // Surrogates are not valid characters
+        assert!(run("char_from_code(55296)")
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn count_occurrences() -> Result<(), InterpError> {
+        assert_eq!(run("count(as_list(1, 2, 1, 3, 1), 1)")?, Expr::Integer(3));
+        assert_eq!(
+            run(r#"count(as_list("a", 1, "a"), "a")"#)?,
+            Expr::Integer(2)
+        );
+        assert_eq!(run("count(as_list(), 1)")?, Expr::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn escape_versus_literal() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let string = Expr::String("line\nnext".to_string());
+
+        println(std::slice::from_ref(&string), &mut env)?;
+        let escaped = escape(&[string], &mut env)?;
+        println(&[escaped], &mut env)?;
+
+        assert_eq!(env.global.output, vec!["line\nnext\n", "line\\nnext\n"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_limit() {
+        let mut env = Interpreter::new(true, true, Some(10));
+        // Prints forever without the limit, the multibyte char is not split
+        let ast = parser::parse(
+            "test",
+            r#"{ def spam = lambda() { print("ab", "é"); spam() }; spam() }"#,
+        )
+        .unwrap();
+        assert!(Expr::eval(&ast, &mut env)
+            .is_err_and(|e| matches!(e, InterpError::OutputLimitExceeded { limit: 10 })));
+        assert_eq!(
+            env.global.output.concat(),
+            format!("abéabéab{}", Environment::TRUNCATION_MARKER)
+        );
+
+        // Further output is dropped
+        assert!(print(&[Expr::Integer(1)], &mut env).is_err());
+        assert!(env
+            .global
+            .output
+            .concat()
+            .ends_with(Environment::TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn output_hook() -> Result<(), InterpError> {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let mut env = Interpreter::new(true, true, None);
+        let hook_seen = seen.clone();
+        env.global.output_hook = Some(OutputHook::new(move |output: &str| {
+            hook_seen.borrow_mut().push(output.to_string())
+        }));
+
+        let ast = parser::parse("test", r#"{ print("a", 1); println("b", 2); dbg(3) }"#).unwrap();
+        Expr::eval(&ast, &mut env)?;
+        // Called for every piece of output in order, as it is stored
+        assert_eq!(*seen.borrow(), vec!["a", "1", "b 2\n", "Integer(3)\n"]);
+        assert_eq!(seen.borrow().concat(), env.global.output.concat());
+
+        Ok(())
+    }
+
+    #[test]
+    fn assertions() -> Result<(), InterpError> {
+        assert_eq!(run("assert(equal?(1, 1))")?, Expr::Boolean(true));
+        assert_eq!(run("assert_equal(add(1, 2), 3)")?, Expr::Boolean(true));
+
+        assert!(run("assert(1)").is_err_and(|e| matches!(e, InterpError::AssertionError { .. })));
+        let err = run(r#"assert_equal(concat("a", "b"), "ba")"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Assertion failed: values are not equal\n  left: \"ab\"\n right: \"ba\""
+        );
+        assert!(
+            run("assert_equal(1)").is_err_and(|e| matches!(e, InterpError::ArgumentError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn user_error() -> Result<(), InterpError> {
+        assert!(run(r#"error("boom")"#)
+            .is_err_and(|e| matches!(e, InterpError::UserError { message } if message == "boom")));
+        assert_eq!(
+            run(r#"try { error("boom"); 1 } catch e { concat("caught ", e) }"#)?,
+            Expr::String("caught Error: boom".to_string())
+        );
+        assert!(run("error(1)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn copy_list() -> Result<(), InterpError> {
+        let result = run(r#"{
+            let original = [1, [2, 3]];
+            let copied = copy(original);
+            let changed = set(copied, 0, 9);
+            [original, copied, changed]
+        }"#)?;
+        assert_eq!(
+            result.to_string(),
+            "[[1, [2, 3]], [1, [2, 3]], [9, [2, 3]]]"
+        );
+
+        // The copy, including nested lists, does not share storage with the original
+        let original = Expr::List(Rc::new(vec![Expr::List(Rc::new(vec![Expr::Integer(1)]))]));
+        let mut env = Interpreter::new(true, false, None);
+        let (Expr::List(original_list), Expr::List(copied_list)) =
+            (&original, &copy(std::slice::from_ref(&original), &mut env)?)
+        else {
+            panic!("copy of a list should be a list");
+        };
+        assert!(!Rc::ptr_eq(original_list, copied_list));
+        assert!(matches!(
+            (&original_list[0], &copied_list[0]),
+            (Expr::List(a), Expr::List(b)) if !Rc::ptr_eq(a, b)
+        ));
+        assert_eq!(run("copy(5)")?, Expr::Integer(5));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "parser")]
+    fn eval_source() -> Result<(), InterpError> {
+        assert_eq!(run(r#"eval("add(1, 2)")"#)?, Expr::Integer(3));
+        // The source sees and changes the current environment
+        assert_eq!(
+            run(r#"{ def n = 4; eval("def m = mul(n, 2)"); m }"#)?,
+            Expr::Integer(8)
+        );
+        assert_eq!(
+            run(r#"eval("add(1,")"#).unwrap_err().to_string(),
+            "Parse error: Error while parsing at line 1, column 7! Expected expression here"
+        );
+
+        // A program evaluating itself is stopped instead of overflowing the stack
+        let result = run(r#"{ def source = "eval(source)"; eval(source) }"#);
+        assert!(result
+            .is_err_and(|e| matches!(e, InterpError::ValueError { func, .. } if func == "eval")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn structured_comparison() -> Result<(), InterpError> {
+        assert_eq!(run(r#"less?("apple", "banana")"#)?, Expr::Boolean(true));
+        assert_eq!(run(r#"greater?("apple", "apples")"#)?, Expr::Boolean(false));
+        assert_eq!(run("less?([1, 2, 3], [1, 3, 0])")?, Expr::Boolean(true));
+        assert_eq!(run("greater?([1, 2, 3], [1, 2, 3])")?, Expr::Boolean(false));
+        assert_eq!(run("less?([1, [2]], [1, [2], 0])")?, Expr::Boolean(true));
+        assert_eq!(run("greater?(2, to_float(1))")?, Expr::Boolean(true));
+
+        assert!(run(r#"less?(1, "1")"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+        assert!(
+            run(r#"less?([1], ["a"])"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+        assert!(
+            run("less?(true, false)").is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+        assert!(run("less?(1)").is_err_and(|e| matches!(e, InterpError::ArgumentError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn three_way_comparison() -> Result<(), InterpError> {
+        assert_eq!(run("compare(1, 2)")?, Expr::Integer(-1));
+        assert_eq!(run("compare(2, 2)")?, Expr::Integer(0));
+        assert_eq!(run("compare(3, to_float(2))")?, Expr::Integer(1));
+        assert_eq!(run(r#"compare("b", "a")"#)?, Expr::Integer(1));
+        assert_eq!(run(r#"compare("a", "ab")"#)?, Expr::Integer(-1));
+        assert_eq!(run("compare([1, [2]], [1, [2]])")?, Expr::Integer(0));
+        assert_eq!(run("compare([1, 3], [1, 2, 0])")?, Expr::Integer(1));
+
+        assert!(
+            run(r#"compare(1, "1")"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+        assert!(
+            run("compare(true, false)").is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn println_separator() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let a = Expr::String("a".to_string());
+        println(std::slice::from_ref(&a), &mut env)?;
+        println(&[a.clone(), Expr::Integer(1), a.clone()], &mut env)?;
+        println(&[], &mut env)?;
+        print(&[a.clone(), a], &mut env)?;
+
+        assert_eq!(env.global.output.concat(), "a\na 1 a\n\naa");
+
+        Ok(())
+    }
+
+    #[test]
+    fn println_modes_match() -> Result<(), InterpError> {
+        let args = [
+            Expr::String("a".to_string()),
+            Expr::List(Rc::new(vec![Expr::Integer(1), Expr::Integer(2)])),
+            Expr::Float(0.5),
+        ];
+        let mut env = Interpreter::new(true, true, None);
+        println(&args, &mut env)?;
+
+        // Direct mode prints println_line, so the captured output has the same single newline
+        assert_eq!(env.global.output.concat(), println_line(&args));
+        assert_eq!(println_line(&args), "a [1, 2] 0.5\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn dbg_modes_match() -> Result<(), InterpError> {
+        let arg = Expr::List(Rc::new(vec![
+            Expr::String("a\nb".to_string()),
+            Expr::Integer(1),
+        ]));
+        let mut env = Interpreter::new(true, true, None);
+        dbg(std::slice::from_ref(&arg), &mut env)?;
+
+        // Direct mode prints dbg_line, so the captured output is the same clean debug form
+        assert_eq!(env.global.output.concat(), dbg_line(&arg));
+        assert_eq!(
+            dbg_line(&arg),
+            "List([String(a\\nb), Integer(1)]), Length=2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn inspect_returns_argument() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let ast = parser::parse("test", "5 |> inspect |> add(1)").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(6));
+        assert_eq!(env.global.output.concat(), "5\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn function_arity() -> Result<(), InterpError> {
+        assert_eq!(run("arity(lambda(a, b) { add(a, b) })")?, Expr::Integer(2));
+        assert_eq!(run("arity(lambda() { 1 })")?, Expr::Integer(0));
+        // Builtins taking a fixed number of arguments report it, others are nil
+        assert_eq!(run("arity(length)")?, Expr::Integer(1));
+        assert_eq!(run("arity(arity)")?, Expr::Integer(1));
+        assert_eq!(run("arity(add)")?, Expr::Nil);
+        assert_eq!(run("arity(string_replace)")?, Expr::Nil);
+        assert!(run("arity(1)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_types() -> Result<(), InterpError> {
+        let program = |call: &str| {
+            format!(
+                "{{ let repeat = lambda(s: string, n: integer, sep) {{ s }}; {} }}",
+                call
+            )
+        };
+        assert_eq!(
+            run(&program(r#"repeat("a", 2, 0)"#))?,
+            Expr::String("a".to_string())
+        );
+
+        let error = run(&program(r#"repeat("a", "2", 0)"#)).unwrap_err();
+        assert!(matches!(
+            &error,
+            InterpError::ParameterTypeError { param, expected, .. } if param == "n" && expected == "integer"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Type error: parameter 'n' of function 'Anonymous' expected integer, found \"2\""
+        );
+        // Unannotated parameters take anything, number takes both integers and floats
+        assert_eq!(
+            run("lambda(x: number) { x }(to_float(1))")?,
+            Expr::Float(1.0)
+        );
+        assert_eq!(
+            run("lambda(a: list, b) { a }")?.to_string(),
+            "function: Anonymous(a: list, b)"
+        );
+        assert!(
+            run("lambda(a: map) { a }").is_err_and(|e| matches!(e, InterpError::ParseError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn results() -> Result<(), InterpError> {
+        let safe_div = r#"def safe_div = lambda(a, b) {
+            cond (zero?(b) => err("division by zero")) (true => ok(div(a, b)))
+        }"#;
+        assert_eq!(
+            run(&format!("{{ {}; unwrap(safe_div(6, 3)) }}", safe_div))?,
+            Expr::Integer(2)
+        );
+        assert_eq!(
+            run(&format!("{{ {}; is_ok?(safe_div(6, 0)) }}", safe_div))?,
+            Expr::Boolean(false)
+        );
+        assert_eq!(run("is_ok?(ok(0))")?, Expr::Boolean(true));
+        // Results are plain lists
+        assert_eq!(run("err(1)")?.to_string(), r#"["Err", 1]"#);
+        assert!(run(r#"unwrap(err("bad"))"#).is_err_and(|e| {
+            e.to_string() == "Invalid value supplied to function 'unwrap': called on an err: bad"
+        }));
+        assert!(
+            run("unwrap(as_list(1, 2))").is_err_and(|e| matches!(e, InterpError::TypeError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn doc_strings() -> Result<(), InterpError> {
+        let documented = r#"{
+            let square = lambda(n) { "Multiplies a number by itself"; mul(n, n) };
+            as_list(doc(square), square(3))
+        }"#;
+        assert_eq!(
+            run(documented)?,
+            Expr::List(Rc::new(vec![
+                Expr::String("Multiplies a number by itself".to_string()),
+                Expr::Integer(9),
+            ]))
+        );
+        assert_eq!(run("doc(lambda(n) { mul(n, n) })")?, Expr::Nil);
+        // A lone string is what the function returns
+        assert_eq!(run(r#"doc(lambda() { "result" })"#)?, Expr::Nil);
+        assert_eq!(
+            run("doc(add)")?,
+            Expr::String("Sum of integers".to_string())
+        );
+        assert!(run("doc(1)").is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn compose_functions() -> Result<(), InterpError> {
+        let result = run(r#"{
+            let strip = lambda(s) { string_replace(s, " ", "") };
+            let shout = compose(to_uppercase, strip);
+            shout(" hello world ")
+        }"#)?;
+        assert_eq!(result, Expr::String("helloworld".to_uppercase()));
+
+        // The composed function takes exactly one argument
+        assert_eq!(
+            run("arity(compose(to_uppercase, to_lowercase))")?,
+            Expr::Integer(1)
+        );
+        assert!(run("compose(to_uppercase, 1)")
+            .is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn application_order() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let mut eval = |input: &str| {
+            let ast = parser::parse("test", input).unwrap();
+            Expr::eval(&ast, &mut env)
+        };
+
+        // The function is evaluated first, then the arguments from left to right
+        eval(
+            r#"{ let f = lambda(a, b) { print("f") }; { print("callee"); f }(print("a"), print("b")) }"#,
+        )?;
+        // The first error stops evaluation, later arguments and the call never happen
+        assert!(eval(r#"add(print("c"), div(1, 0), print("not printed"))"#).is_err());
+        // Nothing is evaluated after a callee that is not a function
+        assert!(eval(r#"{ print("d"); 1 }(print("not printed"))"#)
+            .is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        assert_eq!(env.global.output.concat(), "calleeabfcd");
+
+        Ok(())
+    }
+
+    #[test]
+    fn identity_and_constantly() -> Result<(), InterpError> {
+        assert_eq!(run("identity(5)")?, Expr::Integer(5));
+        assert_eq!(run("find([false, true], identity)")?, Expr::Boolean(true));
+
+        assert_eq!(
+            run("{ let seven = constantly(7); seven() }")?,
+            Expr::Integer(7)
+        );
+        assert_eq!(run("arity(constantly(7))")?, Expr::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bound_names() -> Result<(), InterpError> {
+        let Expr::List(names) = run("{ let a = 1; let add = 2; env_names() }")? else {
+            panic!("env_names should return a list");
+        };
+        // Innermost binding comes first, and the shadowed builtin is listed once
+        assert_eq!(names[0], Expr::String("add".to_string()));
+        assert!(names.contains(&Expr::String("a".to_string())));
+        assert!(names.contains(&Expr::String("println".to_string())));
+        assert_eq!(
+            names
+                .iter()
+                .filter(|name| **name == Expr::String("add".to_string()))
+                .count(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cloned_function_shares_block() -> Result<(), InterpError> {
+        let (expr, mut env) = interpret_default(
+            parser::parse("test", "lambda(n) { mul(n, 2) }").unwrap(),
+            true,
+            false,
+        )?;
+        let Expr::Function(func) = &expr else {
+            panic!("lambda should evaluate to a function");
+        };
+        let Expr::Function(cloned) = expr.clone() else {
+            unreachable!()
+        };
+
+        match (func, &cloned) {
+            (
+                Function::Function { args, func, .. },
+                Function::Function {
+                    args: cloned_args,
+                    func: cloned_func,
+                    ..
+                },
+            ) => {
+                assert!(Rc::ptr_eq(args, cloned_args));
+                assert!(Rc::ptr_eq(func, cloned_func));
+            }
+            _ => panic!("lambda should be a user function"),
+        }
+
+        // Both behave the same
+        assert_eq!(cloned, *func);
+        assert_eq!(
+            apply_function(func, &[Expr::Integer(4)], &mut env)?,
+            apply_function(&cloned, &[Expr::Integer(4)], &mut env)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn integer_float_equality() -> Result<(), InterpError> {
+        // The parser has no float literals, so use the JSON AST directly
+        let ast = serde_json::json!({ "Application": [{ "Identifier": "equal?" }, 1, 1.0] });
+        assert_eq!(interpret_default(ast, true, false)?.0, Expr::Boolean(true));
+        let ast = serde_json::json!({ "Application": [{ "Identifier": "equal?" }, 1, 1.5] });
+        assert_eq!(interpret_default(ast, true, false)?.0, Expr::Boolean(false));
+
+        let mut env = Interpreter::new(true, false, None);
+        let nan = Expr::Float(f64::NAN);
+        assert_eq!(eq(&[nan.clone(), nan], &mut env)?, Expr::Boolean(false));
+        // Integers too large to be exact as a float are not equal to the rounded float
+        assert_eq!(
+            eq(
+                &[Expr::Integer(i64::MAX), Expr::Float(i64::MAX as f64)],
+                &mut env
+            )?,
+            Expr::Boolean(false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn numeric_conversions() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let float = |f: f64| [Expr::Float(f)];
+
+        // Truncation towards zero
+        assert_eq!(to_int(&float(2.9), &mut env)?, Expr::Integer(2));
+        assert_eq!(to_int(&float(-2.9), &mut env)?, Expr::Integer(-2));
+        assert_eq!(run(r#"to_int(" 42 ")"#)?, Expr::Integer(42));
+        assert!(run(r#"to_int("4.2")"#).is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(to_int(&float(f64::NAN), &mut env)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        assert!(to_int(&float(1e19), &mut env)
+            .is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+
+        // Halves round away from zero
+        assert_eq!(round(&float(2.5), &mut env)?, Expr::Integer(3));
+        assert_eq!(round(&float(-2.5), &mut env)?, Expr::Integer(-3));
+        assert_eq!(round(&float(2.4), &mut env)?, Expr::Integer(2));
+        assert_eq!(floor(&float(-1.5), &mut env)?, Expr::Integer(-2));
+        assert_eq!(ceil(&float(-1.5), &mut env)?, Expr::Integer(-1));
+        assert_eq!(run("floor(7)")?, Expr::Integer(7));
+
+        // Round trips
+        assert_eq!(run("to_float(3)")?, Expr::Float(3.0));
+        assert_eq!(run("to_int(to_float(-12))")?, Expr::Integer(-12));
+        assert_eq!(
+            to_float(&[to_int(&float(8.0), &mut env)?], &mut env)?,
+            Expr::Float(8.0)
+        );
+        assert!(run(r#"to_float("3")"#).is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn char_at_index() -> Result<(), InterpError> {
+        assert_eq!(run(r#"char_at("hello", 1)"#)?, Expr::Char('e'));
+        assert_eq!(run(r#"char_at("hello", -1)"#)?, Expr::Char('o'));
+        // Indexes characters, not bytes
+        assert_eq!(run(r#"char_at("añλb", 2)"#)?, Expr::Char('λ'));
+        assert_eq!(run(r#"char_at("añλb", -3)"#)?, Expr::Char('ñ'));
+        // Characters work with string builtins, but are not equal to strings
+        assert_eq!(
+            run(r#"concat(char_at("hello", 0), "i")"#)?,
+            Expr::String("hi".to_string())
+        );
+        assert_eq!(run(r#"contains('l', "hello")"#)?, Expr::Boolean(true));
+        assert_eq!(run(r#"equal?('h', "h")"#)?, Expr::Boolean(false));
+
+        for out_of_range in [
+            r#"char_at("añλb", 4)"#,
+            r#"char_at("añλb", -5)"#,
+            r#"char_at("", 0)"#,
+        ] {
+            assert!(run(out_of_range).is_err_and(|e| matches!(e, InterpError::ValueError { .. })));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn take_and_drop() -> Result<(), InterpError> {
+        assert_eq!(run("take(as_list(1, 2, 3), 2)")?, int_list(&[1, 2]));
+        assert_eq!(run("drop(as_list(1, 2, 3), 2)")?, int_list(&[3]));
+        // Overshooting the length is clamped
+        assert_eq!(run("take(as_list(1, 2, 3), 5)")?, int_list(&[1, 2, 3]));
+        assert_eq!(run("drop(as_list(1, 2, 3), 5)")?, int_list(&[]));
+        assert_eq!(run("take(as_list(1, 2, 3), 0)")?, int_list(&[]));
+        assert_eq!(run("drop(as_list(1, 2, 3), 0)")?, int_list(&[1, 2, 3]));
+
+        assert!(
+            run("take(as_list(1), -1)").is_err_and(|e| matches!(e, InterpError::ValueError { .. }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_predicates() -> Result<(), InterpError> {
+        let program = |func: &str, list: &str| {
+            run(&format!(
+                "{{ let even = lambda(n) {{ zero?(rem(n, 2)) }}; {}(as_list({}), even) }}",
+                func, list
+            ))
+        };
+
+        assert_eq!(program("find", "1, 4, 6")?, Expr::Integer(4));
+        assert_eq!(program("find", "1, 3")?, Expr::Nil);
+        assert_eq!(program("any?", "1, 4")?, Expr::Boolean(true));
+        assert_eq!(program("any?", "1, 3")?, Expr::Boolean(false));
+        assert_eq!(program("all?", "2, 4")?, Expr::Boolean(true));
+        assert_eq!(program("all?", "2, 3")?, Expr::Boolean(false));
+
+        // Empty lists
+        assert_eq!(program("find", "")?, Expr::Nil);
+        assert_eq!(program("any?", "")?, Expr::Boolean(false));
+        assert_eq!(program("all?", "")?, Expr::Boolean(true));
+
+        // The predicate must give a boolean
+        assert!(run("any?(as_list(1), lambda(n) { n })")
+            .is_err_and(|e| matches!(e, InterpError::TypeError { .. })));
+
+        Ok(())
+    }
+}
@@ -1,28 +1,170 @@
 use std::{cell::RefCell, fmt, rc::Rc};
 
-use serde_json::{Map, Value};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
 use crate::{
-    environment::{Environment, LocalEnvironment},
+    ast::Ast,
+    environment::{BuiltinInfo, BuiltinSet, Environment, LocalEnvironment, SavedBindings},
     error::InterpError,
-    functions::{function_application, parse_anonymous_function, Function},
+    functions::{create_function, function_application, Function},
+    random::Rng,
 };
 
 /// Holds current and global environments
 pub struct Interpreter {
     pub global: Environment,
     pub local: Rc<RefCell<LocalEnvironment>>,
+    // Work done by every evaluation since the interpreter was created or reset
+    pub stats: Stats,
+    // How many function applications are running inside of each other
+    pub(crate) call_depth: usize,
+    // Groups of builtins the interpreter was created with, restored by reset
+    builtin_set: BuiltinSet,
+}
+
+/// Counts of the work done evaluating programs, for profiling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    // Expressions evaluated
+    pub evals: u64,
+    // Functions applied, both builtins and user functions
+    pub applications: u64,
+    // Most function applications that were running inside of each other
+    pub max_depth: usize,
+}
+
+/// State of an interpreter saved by Interpreter::snapshot, which Interpreter::restore returns to
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    local: Rc<RefCell<LocalEnvironment>>,
+    // Bindings of every environment reachable from local, which assignment could change
+    bindings: Vec<SavedBindings>,
+    output_count: usize,
+    output_len: usize,
 }
 
 impl Interpreter {
-    pub fn new(lexical_scope: bool, store_output: bool) -> Self {
+    /// Limit of stored output (in bytes) used when interpreting for WASM
+    pub const DEFAULT_OUTPUT_LIMIT: usize = 1 << 20;
+
+    /// Most function applications that can run inside of each other before evaluation stops with a stack overflow error
+    /// A release build uses about 2KB of stack per application, which keeps this well within the 1MB stack of WASM
+    pub const MAX_CALL_DEPTH: usize = 256;
+
+    /// Creates an interpreter, output_limit is the most output (in bytes) to store when store_output is set
+    pub fn new(lexical_scope: bool, store_output: bool, output_limit: Option<usize>) -> Self {
+        Self::with_rng(
+            lexical_scope,
+            store_output,
+            output_limit,
+            BuiltinSet::full(),
+            Rng::from_entropy(),
+        )
+    }
+
+    /// Creates an interpreter with only the selected groups of builtins available
+    pub fn with_builtins(
+        lexical_scope: bool,
+        store_output: bool,
+        output_limit: Option<usize>,
+        builtins: BuiltinSet,
+    ) -> Self {
+        Self::with_rng(
+            lexical_scope,
+            store_output,
+            output_limit,
+            builtins,
+            Rng::from_entropy(),
+        )
+    }
+
+    /// Creates an interpreter whose random numbers are reproducible from the seed
+    pub fn with_seed(
+        lexical_scope: bool,
+        store_output: bool,
+        output_limit: Option<usize>,
+        seed: u64,
+    ) -> Self {
+        Self::with_rng(
+            lexical_scope,
+            store_output,
+            output_limit,
+            BuiltinSet::full(),
+            Rng::from_seed(seed),
+        )
+    }
+
+    fn with_rng(
+        lexical_scope: bool,
+        store_output: bool,
+        output_limit: Option<usize>,
+        builtins: BuiltinSet,
+        rng: Rng,
+    ) -> Self {
+        let local = LocalEnvironment::with_builtins(builtins);
         let global = Environment {
             lexical_scope,
+            undefined_as_symbol: false,
             store_output,
             output: Vec::new(),
+            output_limit,
+            output_len: 0,
+            rng,
+            eval_depth: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+            builtins: local.builtin_info(),
+            output_hook: None,
         };
-        let local = Rc::new(RefCell::new(LocalEnvironment::default_environment()));
-        Self { global, local }
+        let local = Rc::new(RefCell::new(local));
+        Self {
+            global,
+            local,
+            stats: Stats::default(),
+            call_depth: 0,
+            builtin_set: builtins,
+        }
+    }
+
+    /// Discards every binding made by programs, all stored output, and the stats, keeping the settings and builtins
+    pub fn reset(&mut self) {
+        self.local = Rc::new(RefCell::new(LocalEnvironment::with_builtins(
+            self.builtin_set,
+        )));
+        self.global.output.clear();
+        self.global.output_len = 0;
+        self.global.eval_depth = 0;
+        self.stats = Stats::default();
+        self.call_depth = 0;
+    }
+
+    /// Saves the bindings and stored output, so an evaluation can be tried and undone with restore
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            local: self.local.clone(),
+            bindings: LocalEnvironment::save_reachable(&self.local),
+            output_count: self.global.output.len(),
+            output_len: self.global.output_len,
+        }
+    }
+
+    /// Returns to the state saved by snapshot, discarding bindings, assignments, and output made since
+    /// Output already printed (rather than stored) or passed to the output hook cannot be taken back
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        for bindings in snapshot.bindings {
+            bindings.restore();
+        }
+        self.local = snapshot.local;
+        self.global.output.truncate(snapshot.output_count);
+        self.global.output_len = snapshot.output_len;
+        self.global.eval_depth = 0;
+        self.call_depth = 0;
+    }
+
+    /// Name, arity, and doc string of every builtin the interpreter was created with, sorted by name
+    pub fn builtins(&self) -> Vec<BuiltinInfo> {
+        self.global.builtins.clone()
     }
 
     /// Enters a new, blank, local environment
@@ -47,146 +189,242 @@ impl Interpreter {
 }
 
 /// All the types of the language
-#[derive(PartialEq, Eq, Clone)]
+#[derive(Clone)]
 pub enum Expr {
     // Integer value
     Integer(i64),
+    // Floating point value
+    Float(f64),
     // Boolean
     Boolean(bool), // true, false
     // String value
     String(String),
-    // List of Expr
-    List(Vec<Expr>),
+    // Single character
+    Char(char),
+    // List of Expr, shared so that cloning a list does not copy its elements
+    // Lists are values, builtins like set return a new list so a change is never visible through another binding
+    List(Rc<Vec<Expr>>),
+    // Cell of a linked list built by cons, adding to the front or taking the rest shares cells instead of copying
+    Pair(Rc<Pair>),
     // Function
     Function(Function),
+    // Absence of a value
+    Nil,
+}
+
+/// Element and rest of a linked list, the rest is another pair or nil at the end
+#[derive(Clone)]
+pub struct Pair {
+    pub head: Expr,
+    pub rest: Expr,
+}
+
+impl Pair {
+    /// Elements of the linked list starting at this pair
+    pub fn iter(&self) -> PairIter<'_> {
+        PairIter(Some(self))
+    }
+}
+
+// Unlinks the rest one pair at a time, so dropping a long linked list cannot overflow the stack
+impl Drop for Pair {
+    fn drop(&mut self) {
+        let mut rest = std::mem::replace(&mut self.rest, Expr::Nil);
+        while let Expr::Pair(pair) = rest {
+            match Rc::try_unwrap(pair) {
+                Ok(mut pair) => rest = std::mem::replace(&mut pair.rest, Expr::Nil),
+                // Still shared, the other owner keeps the rest alive
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Iterator over the elements of a linked list of pairs
+pub struct PairIter<'a>(Option<&'a Pair>);
+
+impl<'a> Iterator for PairIter<'a> {
+    type Item = &'a Expr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pair = self.0?;
+        self.0 = match &pair.rest {
+            Expr::Pair(rest) => Some(rest),
+            _ => None,
+        };
+        Some(&pair.head)
+    }
 }
 
 impl Expr {
+    /// Compiles a JSON AST and evaluates it
     pub fn eval(
         val: &serde_json::Value,
         interpreter: &mut Interpreter,
     ) -> Result<Expr, InterpError> {
-        match val {
-            Value::Number(num) => num
-                .as_i64()
-                .ok_or_else(|| InterpError::TypeError {
-                    expected: "i64".to_string(),
-                    found: num.to_string(),
-                })
-                .map(|i| Expr::Integer(i)),
-            Value::Bool(bool) => Ok(Expr::Boolean(*bool)),
-            Value::String(string) => {
-                return Ok(Expr::String(string.to_string()));
-            }
-            Value::Array(arr) => Ok(Expr::List(
-                arr.into_iter()
-                    .map(|val| Expr::eval(val, interpreter))
-                    .collect::<Result<Vec<Expr>, InterpError>>()?,
-            )),
-            Value::Object(obj) => interpret_object(obj, interpreter),
-            _ => Err(InterpError::ParseError {
-                message: format!(
-                    "{} is not an implemented type! It is of JSON type {:?}",
-                    val, val
-                ),
-            }),
-        }
+        Expr::eval_ast(&Ast::from_value(val)?, interpreter)
     }
-}
 
-/// Interpret a JSON object, looking for the keys that correspond to certain behaviors
-fn interpret_object(
-    obj: &Map<String, Value>,
-    interpreter: &mut Interpreter,
-) -> Result<Expr, InterpError> {
-    // First see if there is an identifier
-    if let Some(binding) = obj.get("Identifier").and_then(|val| val.as_str()) {
-        return interpreter.local.borrow().lookup(binding).ok_or_else(|| {
-            InterpError::UndefinedError {
-                symbol: binding.to_string(),
+    /// Evaluates a compiled AST
+    pub fn eval_ast(ast: &Ast, interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+        interpreter.stats.evals += 1;
+        Expr::eval_node(ast, interpreter)
+    }
+
+    /// Evaluates a single node of a compiled AST, which eval_ast counts
+    fn eval_node(ast: &Ast, interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+        match ast {
+            Ast::Integer(i) => Ok(Expr::Integer(*i)),
+            Ast::Float(f) => Ok(Expr::Float(*f)),
+            Ast::Boolean(bool) => Ok(Expr::Boolean(*bool)),
+            Ast::String(string) => Ok(Expr::String(string.to_string())),
+            Ast::Char(c) => Ok(Expr::Char(*c)),
+            Ast::List(arr) => Ok(Expr::List(Rc::new(
+                arr.iter()
+                    .map(|ast| Expr::eval_ast(ast, interpreter))
+                    .collect::<Result<Vec<Expr>, InterpError>>()?,
+            ))),
+            Ast::Identifier(binding) => {
+                let local = interpreter.local.borrow();
+                match local.lookup(binding) {
+                    Some(value) => Ok(value),
+                    // Lenient mode for symbolic programs, the name stands for itself
+                    None if interpreter.global.undefined_as_symbol => {
+                        Ok(Expr::String(binding.clone()))
+                    }
+                    None => Err(local.undefined_error(binding)),
+                }
             }
-        });
-    }
-
-    // Handle blocks
-    if let Some(key) = obj.get("Block") {
-        return interpret_block(key, interpreter, None);
-    } else if let Some(lambda) = obj.get("Lambda") {
-        return parse_anonymous_function(lambda, None, interpreter);
-    } else if let Some(arr) = obj.get("Application") {
-        return function_application(arr, interpreter);
-    } else if let Some(arr) = obj.get("Cond") {
-        if let Value::Array(arr) = arr {
-            // Expect "Clause"
-            // Returns the result of the first expression where it's condition was true
-            for statement in arr {
-                if let Value::Array(clause) = statement.get("Clause").expect("Expect \"Clause\"") {
-                    // Splits the condition and expression away
-                    let [condition, expr] = clause.as_slice() else {
-                        return Err(InterpError::ParseError {
-                            message: "Clause did not contain both a condition and expression."
-                                .to_string(),
-                        });
-                    };
-                    // Store condition result
-                    let condition = Expr::eval(condition, interpreter)?;
-                    // If it is a boolean that is true, we evaluate the expression
-                    if let Expr::Boolean(b) = condition {
-                        if b {
-                            return Expr::eval(expr, interpreter);
-                        }
-                    } else {
-                        return Err(InterpError::TypeError {
-                            expected: "bool".to_string(),
-                            found: condition.to_string(),
-                        });
+            Ast::Block(block) => interpret_block(block, interpreter, None),
+            Ast::Lambda {
+                params,
+                types,
+                block,
+                doc,
+            } => Ok(create_function(
+                params,
+                types,
+                block,
+                doc.clone(),
+                None,
+                interpreter,
+            )),
+            Ast::Application(arr) => function_application(arr, interpreter),
+            Ast::Cond(clauses) => {
+                // Returns the result of the first expression where it's condition was true
+                for (condition, expr) in clauses {
+                    // If the condition is true, we evaluate the expression
+                    if Expr::eval_ast(condition, interpreter)?.is_truthy()? {
+                        return Expr::eval_ast(expr, interpreter);
                     }
-                    continue;
                 }
+                Ok(Expr::Boolean(false))
             }
-        }
-        return Ok(Expr::Boolean(false));
-    } else if let Some(arr) = obj.get("Let") {
-        let (name, var, block) = interpret_var(arr, interpreter, "let")?;
-        match block {
-            None => {
+            Ast::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                // The value is evaluated once, then cases in order until one is equal to it
+                let value = Expr::eval_ast(value, interpreter)?;
+                for (case, expr) in cases {
+                    if Expr::eval_ast(case, interpreter)? == value {
+                        return Expr::eval_ast(expr, interpreter);
+                    }
+                }
+                // Like cond without a true clause, false without a default
+                match default {
+                    Some(default) => Expr::eval_ast(default, interpreter),
+                    None => Ok(Expr::Boolean(false)),
+                }
+            }
+            Ast::Let { name, value, block } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                match block {
+                    None => {
+                        interpreter.enter_new_local();
+                        interpreter.local.borrow_mut().bind(vec![(name, &var)]);
+                        Ok(var)
+                    }
+                    Some(b) => interpret_block(b, interpreter, Some(vec![(name, &var)])),
+                }
+            }
+            Ast::Destructure { names, value } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                let Expr::List(elements) = &var else {
+                    return Err(InterpError::TypeError {
+                        expected: "list".to_string(),
+                        found: var.to_string(),
+                    });
+                };
+                if elements.len() != names.len() {
+                    return Err(InterpError::ValueError {
+                        func: "let".to_string(),
+                        message: format!(
+                            "cannot bind {} names to a list of length {}",
+                            names.len(),
+                            elements.len()
+                        ),
+                    });
+                }
                 interpreter.enter_new_local();
-                interpreter.local.borrow_mut().bind(vec![(&name, &var)]);
-                return Ok(var);
+                interpreter
+                    .local
+                    .borrow_mut()
+                    .bind(names.iter().zip(elements.iter()).collect());
+                Ok(var)
             }
-            Some(b) => return interpret_block(&b, interpreter, Some(vec![(&name, &var)])),
-        }
-    } else if let Some(arr) = obj.get("Def") {
-        let (name, var, block) = interpret_var(arr, interpreter, "def")?;
-        // Place into the current local environment (without new block)
-        match block {
-            None => {
-                interpreter.local.borrow_mut().bind(vec![(&name, &var)]);
-                return Ok(var);
+            Ast::LetIn { name, value, body } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                let old_local = interpreter.enter_new_local();
+                interpreter.local.borrow_mut().bind(vec![(name, &var)]);
+                let result = Expr::eval_ast(body, interpreter);
+                interpreter.local = old_local;
+                result
+            }
+            Ast::Def { name, value, block } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                // Place into the current local environment (without new block)
+                match block {
+                    None => {
+                        interpreter.local.borrow_mut().bind(vec![(name, &var)]);
+                        Ok(var)
+                    }
+                    // this case probably does not happen for def
+                    Some(b) => interpret_block(b, interpreter, Some(vec![(name, &var)])),
+                }
             }
-            Some(b) => {
-                // this case probably does not happen for def
-                return interpret_block(&b, interpreter, Some(vec![(&name, &var)]));
+            Ast::Const { name, value } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                interpreter.local.borrow_mut().bind_const(name, &var);
+                Ok(var)
             }
+            Ast::Try {
+                body,
+                name,
+                handler,
+            } => match interpret_block(body, interpreter, None) {
+                // The handler gets the error message
+                Err(e) if e.is_catchable() => {
+                    let error = Expr::String(e.to_string());
+                    interpret_block(handler, interpreter, Some(vec![(name, &error)]))
+                }
+                result => result,
+            },
+            Ast::Assignment { name, value } => {
+                let var = Expr::eval_ast(value, interpreter)?;
+                // Try to assign
+                interpreter.local.borrow_mut().assignment(name, &var)
+            }
+            Ast::Value(expr) => Ok(expr.clone()),
         }
-    } else if let Some(arr) = obj.get("Assignment") {
-        let (name, var, _block) = interpret_var(arr, interpreter, "assignment")?;
-        // Try to assign
-        return interpreter.local.borrow_mut().assignment(&name, &var);
-    }
-
-    Err(InterpError::ParseError {
-        message: format!(
-            "Found JSON Object in AST but it does not contain a known keyword: {:?}",
-            obj
-        ),
-    })
+    }
 }
 
 /// Interpret a block expression, handling creating a new local environment on the environment's stack
 /// Optionally provide some initial bindings (such as a function's block with arguments)
 pub fn interpret_block(
-    val: &serde_json::Value,
+    block: &[Ast],
     interpreter: &mut Interpreter,
     bindings: Option<Vec<(&String, &Expr)>>,
 ) -> Result<Expr, InterpError> {
@@ -195,60 +433,24 @@ pub fn interpret_block(
         interpreter.local.borrow_mut().bind(b);
     }
 
-    let res = match Expr::eval(&val, interpreter)? {
-        Expr::List(list) => {
-            // Return last of list, or false if empty list
-            Ok(list.last().cloned().unwrap_or(Expr::Boolean(false)))
+    // Return last of block, or false if empty block
+    let mut res = Ok(Expr::Boolean(false));
+    for ast in block {
+        res = Expr::eval_ast(ast, interpreter);
+        if res.is_err() {
+            break;
         }
-        expr => Ok(expr),
-    };
+    }
 
     interpreter.local = old_local;
     res
 }
 
-/// Interprets creating a new variable
-fn interpret_var(
-    val: &serde_json::Value,
-    interpreter: &mut Interpreter,
-    expression_type: &str,
-) -> Result<(String, Expr, Option<Value>), InterpError> {
-    if let Value::Array(arr) = val {
-        let ident_name = &arr[0]
-            .get("Identifier")
-            .and_then(|n| n.as_str())
-            .ok_or_else(|| {
-                return InterpError::ParseError {
-                    message: format!("Expecting an identifier in {expression_type} expression"),
-                };
-            })?;
-
-        let ident_val = Expr::eval(&arr[1], interpreter)?;
-
-        // Expect third to be a block if exists (custom parser does not include block after lets)
-        let block = arr
-            .get(2)
-            .and_then(|v| v.as_object().and_then(|o| o.get("Block")));
-        Ok((ident_name.to_string(), ident_val, block.cloned()))
-    } else {
-        return Err(InterpError::ParseError {
-            message: format!("{val} in variable expression should be JSON array."),
-        });
-    }
-}
-
 impl TryInto<bool> for Expr {
     type Error = InterpError;
 
     fn try_into(self) -> Result<bool, Self::Error> {
-        if let Expr::Boolean(b) = self {
-            Ok(b)
-        } else {
-            Err(InterpError::TypeError {
-                expected: "bool".to_string(),
-                found: self.to_string(),
-            })
-        }
+        self.is_truthy()
     }
 }
 
@@ -270,37 +472,158 @@ impl TryInto<i64> for Expr {
 impl TryInto<String> for Expr {
     type Error = InterpError;
 
+    /// A character is also a string, of one character, so string builtins accept characters
     fn try_into(self) -> Result<String, Self::Error> {
-        if let Expr::String(s) = self {
-            Ok(s.clone())
-        } else {
-            Err(InterpError::TypeError {
+        match self {
+            Expr::String(s) => Ok(s),
+            Expr::Char(c) => Ok(c.to_string()),
+            _ => Err(InterpError::TypeError {
                 expected: "string".to_string(),
                 found: self.to_string(),
-            })
+            }),
+        }
+    }
+}
+
+impl Expr {
+    /// Truth value of the expression used as a condition, by cond, not, and, or, and predicates
+    /// Conditions are strict, only booleans have a truth value, so 0, "", and [] are type errors rather than false
+    pub fn is_truthy(&self) -> Result<bool, InterpError> {
+        match self {
+            Expr::Boolean(b) => Ok(*b),
+            _ => Err(InterpError::TypeError {
+                expected: "bool".to_string(),
+                found: self.to_string(),
+            }),
+        }
+    }
+
+    /// Displays the expression with control characters in strings shown as visible escapes (such as `\n`)
+    /// Used where the boundaries of a string matter more than printing it literally, like dbg
+    pub fn display_escaped(&self) -> String {
+        match self {
+            Expr::String(val) => escape_string(val),
+            Expr::Char(c) => escape_string(&c.to_string()),
+            Expr::List(_) | Expr::Pair(_) => self.repr(),
+            expr => expr.to_string(),
+        }
+    }
+
+    /// Displays the expression unambiguously, with strings quoted and escaped so "true" and true differ
+    /// Used for the elements of lists, and where values of different types are compared, like assert_equal
+    pub fn repr(&self) -> String {
+        match self {
+            Expr::String(val) => format!("\"{}\"", escape_string(val).replace('"', "\\\"")),
+            Expr::Char(c) => format!("'{}'", escape_string(&c.to_string()).replace('\'', "\\'")),
+            Expr::List(list) => {
+                let values: Vec<_> = list.iter().map(|v| v.repr()).collect();
+                format!("[{}]", values.join(", "))
+            }
+            Expr::Pair(pair) => {
+                let values: Vec<_> = pair.iter().map(|v| v.repr()).collect();
+                format!("({})", values.join(", "))
+            }
+            expr => expr.to_string(),
+        }
+    }
+}
+
+fn escape_string(val: &str) -> String {
+    let mut escaped = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&c.escape_unicode().to_string()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Equality of values, used by equal? and anywhere values are compared
+/// Integers and floats are compared by numeric value (1 equals 1.0), and NaN is never equal to anything
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expr::Integer(a), Expr::Integer(b)) => a == b,
+            (Expr::Float(a), Expr::Float(b)) => a == b,
+            (Expr::Integer(i), Expr::Float(f)) | (Expr::Float(f), Expr::Integer(i)) => {
+                // Converting back avoids integers too large for f64 comparing equal to a rounded float
+                *i as f64 == *f && *f as i128 == *i as i128
+            }
+            (Expr::Boolean(a), Expr::Boolean(b)) => a == b,
+            (Expr::String(a), Expr::String(b)) => a == b,
+            // A character is not equal to a string of it, like 1 is not equal to "1"
+            (Expr::Char(a), Expr::Char(b)) => a == b,
+            (Expr::List(a), Expr::List(b)) => lists_equal(a, b),
+            // A linked list is not equal to a list of the same elements, like a char and a string
+            (Expr::Pair(a), Expr::Pair(b)) => a.iter().eq(b.iter()),
+            (Expr::Function(a), Expr::Function(b)) => a == b,
+            (Expr::Nil, Expr::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Compares lists element by element with a stack rather than recursion, so deep nesting cannot overflow
+/// Lengths are compared before any elements, and lists are values that cannot contain themselves, so there are no cycles
+fn lists_equal(a: &[Expr], b: &[Expr]) -> bool {
+    let mut pending = vec![(a, b)];
+    while let Some((a, b)) = pending.pop() {
+        if a.len() != b.len() {
+            return false;
+        }
+        for (a, b) in a.iter().zip(b) {
+            match (a, b) {
+                (Expr::List(a), Expr::List(b)) => pending.push((a, b)),
+                _ if a != b => return false,
+                _ => {}
+            }
         }
     }
+    true
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expr::Integer(val) => write!(fmt, "{}", val),
+            // Debug formatting keeps the decimal point of whole numbers (1.0)
+            Expr::Float(val) => write!(fmt, "{:?}", val),
             Expr::Boolean(val) => write!(fmt, "{}", val),
             Expr::String(val) => write!(fmt, "{}", val),
+            Expr::Char(val) => write!(fmt, "{}", val),
             Expr::List(list) => {
-                let values: Vec<_> = list.iter().map(|v| v.to_string()).collect();
+                let values: Vec<_> = list.iter().map(|v| v.repr()).collect();
                 write!(fmt, "[{}]", values.join(", "))
             }
+            Expr::Pair(_) => write!(fmt, "{}", self.repr()),
+            // Shows the parameters of user functions, core functions do not declare theirs
             Expr::Function(func) => match func {
-                Function::CoreFunction { name, func: _ } => write!(fmt, "function: {}", name),
+                Function::CoreFunction { name, .. } => {
+                    write!(fmt, "function: {} <builtin>", name)
+                }
                 Function::Function {
-                    name,
-                    args: _,
-                    func: _,
-                    env: _,
-                } => write!(fmt, "function: {}", name),
+                    name, args, types, ..
+                } => {
+                    let params: Vec<String> = match types.is_empty() {
+                        true => args.to_vec(),
+                        false => args
+                            .iter()
+                            .zip(types.iter())
+                            .map(|(arg, value_type)| match value_type {
+                                Some(value_type) => format!("{}: {}", arg, value_type),
+                                None => arg.clone(),
+                            })
+                            .collect(),
+                    };
+                    write!(fmt, "function: {}({})", name, params.join(", "))
+                }
             },
+            Expr::Nil => write!(fmt, "nil"),
         }
     }
 }
@@ -309,8 +632,10 @@ impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Expr::Integer(value) => write!(f, "Integer({})", value),
+            Expr::Float(value) => write!(f, "Float({:?})", value),
             Expr::Boolean(value) => write!(f, "Boolean({})", value),
-            Expr::String(value) => write!(f, "String({})", value),
+            Expr::String(value) => write!(f, "String({})", escape_string(value)),
+            Expr::Char(value) => write!(f, "Char({})", escape_string(&value.to_string())),
             Expr::List(values) => {
                 let formatted_values: Vec<String> =
                     values.iter().map(|v| format!("{:?}", v)).collect();
@@ -321,7 +646,13 @@ impl fmt::Debug for Expr {
                     values.len()
                 )
             }
+            Expr::Pair(pair) => {
+                let formatted_values: Vec<String> =
+                    pair.iter().map(|v| format!("{:?}", v)).collect();
+                write!(f, "Pair([{}])", formatted_values.join(", "))
+            }
             Expr::Function(func) => write!(f, "Function({:?})", func),
+            Expr::Nil => write!(f, "Nil"),
         }
     }
 }
@@ -329,10 +660,11 @@ impl fmt::Debug for Expr {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::environment::Arity;
 
     #[test]
     fn parse_valid_integer() -> Result<(), InterpError> {
-        let mut env = Interpreter::new(true, false);
+        let mut env = Interpreter::new(true, false, None);
         assert_eq!(
             Expr::Integer(12),
             Expr::eval(&serde_json::from_str("12").unwrap(), &mut env)?
@@ -347,7 +679,7 @@ mod tests {
 
     #[test]
     fn parse_invalid_integer() -> Result<(), InterpError> {
-        let mut env = Interpreter::new(true, false);
+        let mut env = Interpreter::new(true, false, None);
         // Construct numbers larger and smaller than the range supported
         let big_num = i64::MAX as u64 + 10;
         // (Creating a string version manually less than i64::MIN)
@@ -380,7 +712,7 @@ mod tests {
 
     #[test]
     fn parse_valid_string() -> Result<(), InterpError> {
-        let mut env = Interpreter::new(true, false);
+        let mut env = Interpreter::new(true, false, None);
         assert_eq!(
             Expr::String("rust".to_string()),
             Expr::eval(&serde_json::from_str("\"rust\"").unwrap(), &mut env)?
@@ -392,4 +724,690 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn recursive_function_results() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse(
+            "test",
+            r#"{
+                def fib = λ(n) {
+                    cond
+                        (less?(n, 2) => n)
+                        (true => add(fib(sub(n, 1)), fib(sub(n, 2))))
+                };
+                as_list(fib(10), fib(15), fib(10))
+            }"#,
+        )
+        .unwrap();
+        // Calling the same compiled function repeatedly gives the same results
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[55, 610, 55]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn bind_block_value() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse(
+            "test",
+            r#"{
+                let x = { let a = 2; mul(a, 3) };
+                let y = 0;
+                y = { 1; add(x, 1) };
+                as_list(x, y)
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[6, 7]");
+
+        // Bindings made inside of the block do not leak into the enclosing scope
+        let ast = parser::parse("test", "{ let x = { let a = 2; a }; a }").unwrap();
+        assert!(Expr::eval(&ast, &mut env)
+            .is_err_and(|e| matches!(e, InterpError::UndefinedError { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn free_variable_scope() -> Result<(), InterpError> {
+        // helper's free variable x is bound both where helper is defined and inside of the caller
+        let ast = parser::parse(
+            "test",
+            r#"{
+                let x = 1;
+                def helper = lambda() { x };
+                def caller = lambda() { let x = 2; helper() };
+                caller()
+            }"#,
+        )
+        .unwrap();
+
+        // Lexical scope resolves x from the definition site
+        let mut env = Interpreter::new(true, false, None);
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(1));
+
+        // Dynamic scope resolves x from the caller's environment
+        let mut env = Interpreter::new(false, false, None);
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn shadow_builtin_in_block() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse(
+            "test",
+            r#"{
+                let inner = {
+                    def add = lambda(a, b) { mul(a, b) };
+                    add(2, 3)
+                };
+                as_list(inner, add(2, 3))
+            }"#,
+        )
+        .unwrap();
+        // The def only shadows the builtin inside of the block
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[6, 5]");
+
+        // Inner bindings shadow outer ones until their block exits
+        let ast = parser::parse(
+            "test",
+            "{ def n = 1; let m = { def n = 2; n }; as_list(m, n) }",
+        )
+        .unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[2, 1]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn char_literals() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let mut eval = |input: &str| {
+            let ast = parser::parse("test", input).unwrap();
+            Expr::eval(&ast, &mut env)
+        };
+        assert_eq!(eval("'a'")?, Expr::Char('a'));
+        assert_eq!(eval(r"'\n'")?, Expr::Char('\n'));
+
+        let chars = eval(r#"["a", 'a', '\'']"#)?;
+        assert_eq!(chars.to_string(), r#"["a", 'a', '\'']"#);
+        assert_eq!(format!("{:?}", Expr::Char('\n')), "Char(\\n)");
+        assert!(eval("lambda(c: char) { c }('a')").is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_equality() {
+        let list = |n: i64| Expr::List(Rc::new((0..n).map(Expr::Integer).collect()));
+        assert_eq!(list(100_000), list(100_000));
+        assert_ne!(list(100_000), list(100_001));
+
+        let mut changed: Vec<Expr> = (0..100_000).map(Expr::Integer).collect();
+        changed[99_999] = Expr::Integer(-1);
+        assert_ne!(list(100_000), Expr::List(Rc::new(changed)));
+
+        // Deeply nested lists are compared without recursing
+        let nested = |innermost: i64| {
+            (0..1_000).fold(Expr::Integer(innermost), |expr, _| {
+                Expr::List(Rc::new(vec![expr]))
+            })
+        };
+        assert!(nested(1) == nested(1));
+        assert!(nested(1) != nested(2));
+        // NaN is not equal to itself, even in the same list
+        let nan = Expr::List(Rc::new(vec![Expr::Float(f64::NAN)]));
+        assert!(nan != nan.clone());
+    }
+
+    #[test]
+    fn escaped_display() {
+        let string = Expr::String("a\nb\t\\".to_string());
+        assert_eq!(string.to_string(), "a\nb\t\\");
+        assert_eq!(string.display_escaped(), "a\\nb\\t\\\\");
+        assert_eq!(format!("{:?}", string), "String(a\\nb\\t\\\\)");
+
+        let list = Expr::List(Rc::new(vec![string, Expr::Integer(1)]));
+        assert_eq!(list.display_escaped(), "[\"a\\nb\\t\\\\\", 1]");
+    }
+
+    #[test]
+    fn list_repr() {
+        let list = Expr::List(Rc::new(vec![
+            Expr::Boolean(true),
+            Expr::Integer(1),
+            Expr::String("true".to_string()),
+        ]));
+        assert_eq!(list.to_string(), "[true, 1, \"true\"]");
+        assert_eq!(list.repr(), list.to_string());
+        // Quotes inside strings are escaped so the string's boundaries stay visible
+        assert_eq!(Expr::String("a\"b".to_string()).repr(), "\"a\\\"b\"");
+        assert_eq!(Expr::String("true".to_string()).to_string(), "true");
+    }
+
+    #[test]
+    fn const_binding() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "{ const n = 3; mul(n, 2) }").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(6));
+
+        // Assignment to a const fails, also from an inner block
+        let ast = parser::parse("test", "{ const n = 3; { n = 4 } }").unwrap();
+        assert!(matches!(
+            Expr::eval(&ast, &mut env),
+            Err(InterpError::ImmutableAssignment { symbol }) if symbol == "n"
+        ));
+
+        // A def can still be assigned to
+        let ast = parser::parse("test", "{ def n = 3; n = 4; n }").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn undefined_suggestion() {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse(
+            "test",
+            "{ def fact = lambda(n) { n }; def count_up = 1; fcat(5) }",
+        )
+        .unwrap();
+        let err = Expr::eval(&ast, &mut env).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Undefined symbol 'fcat', did you mean 'fact'?"
+        );
+
+        // Assignment also suggests, and builtins are candidates
+        let ast = parser::parse("test", "{ def total = 1; totl = 2 }").unwrap();
+        assert!(matches!(
+            Expr::eval(&ast, &mut env),
+            Err(InterpError::UndefinedError { suggestion: Some(s), .. }) if s == "total"
+        ));
+        let ast = parser::parse("test", "lenght(\"abc\")").unwrap();
+        assert!(Expr::eval(&ast, &mut env)
+            .is_err_and(|e| e.to_string().ends_with("did you mean 'length'?")));
+
+        // Nothing close enough
+        let ast = parser::parse("test", "unrelated_name").unwrap();
+        assert!(matches!(
+            Expr::eval(&ast, &mut env),
+            Err(InterpError::UndefinedError {
+                suggestion: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn try_catch() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse(
+            "test",
+            "{ let safe_div = lambda(a, b) { try { div(a, b) } catch e { 0 } }; as_list(safe_div(6, 3), safe_div(1, 0)) }",
+        )
+        .unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[2, 0]");
+
+        // The error message is bound in the handler
+        let ast = parser::parse("test", "try { rem(1, 0) } catch e { e }").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?,
+            Expr::String("Arithmetic error in function 'rem': division by zero".to_string())
+        );
+
+        // Errors in the handler are not caught
+        let ast = parser::parse("test", "try { div(1, 0) } catch e { undefined }").unwrap();
+        assert!(Expr::eval(&ast, &mut env)
+            .is_err_and(|e| matches!(e, InterpError::UndefinedError { .. })));
+
+        // Exceeding the output limit cannot be caught
+        let mut env = Interpreter::new(true, true, Some(2));
+        let ast = parser::parse("test", r#"try { print("abc") } catch e { 1 }"#).unwrap();
+        assert!(Expr::eval(&ast, &mut env)
+            .is_err_and(|e| matches!(e, InterpError::OutputLimitExceeded { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cached_lookup_invalidation() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        // Deep enough for lookups of the builtin 'x' to be cached
+        let ast = parser::parse(
+            "test",
+            r#"{
+                let a = 1; let b = 2; let c = 3; let d = 4;
+                def f = lambda() { x };
+                def first = f();
+                def x = 20;
+                def second = f();
+                x = 30;
+                [first, second, f()]
+            }"#,
+        )
+        .unwrap();
+        // A new binding shadows the cached one, and assignment changes the value in place
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[10, 20, 30]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn function_display() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "lambda(a, b) { add(a, b) }").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?.to_string(),
+            "function: Anonymous(a, b)"
+        );
+        let ast = parser::parse("test", "compose(length, to_uppercase)").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?.to_string(),
+            "function: compose(x)"
+        );
+        let ast = parser::parse("test", "add").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?.to_string(),
+            "function: add <builtin>"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reset_bindings() -> Result<(), InterpError> {
+        let mut env = Interpreter::with_builtins(true, true, None, BuiltinSet::minimal());
+        let ast = parser::parse("test", "def total = add(1, 2)").unwrap();
+        Expr::eval(&ast, &mut env)?;
+        env.global.add_output("before")?;
+        assert!(env.local.borrow().lookup("total").is_some());
+
+        env.reset();
+        assert!(env.local.borrow().lookup("total").is_none());
+        assert!(env.global.output.is_empty());
+        // Builtins still work, and only the ones the interpreter was created with
+        let ast = parser::parse("test", "add(1, 2)").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(3));
+        assert!(env.local.borrow().lookup("println").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn environment_debug() {
+        let root = Rc::new(RefCell::new(LocalEnvironment::default_environment()));
+        let mut local = root.clone();
+        for _ in 0..1000 {
+            local = LocalEnvironment::from_parent(local);
+        }
+        local.borrow_mut().bind_const("inner", &Expr::Integer(1));
+        assert_eq!(root.borrow().depth(), 0);
+        assert_eq!(local.borrow().depth(), 1000);
+
+        // Only the innermost environment is shown, not the builtins or the chain of parents
+        assert_eq!(
+            format!("{:?}", local.borrow()),
+            r#"LocalEnvironment { names: ["inner"], constants: ["inner"], depth: 1000, has_parent: true }"#
+        );
+    }
+
+    #[test]
+    fn snapshot_restore() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let run = |env: &mut Interpreter, input: &str| {
+            let ast = parser::parse("test", input).unwrap();
+            Expr::eval(&ast, env)
+        };
+        run(&mut env, "def count = 1")?;
+        run(&mut env, "def bump = lambda() { count = add(count, 1) }")?;
+        let snapshot = env.snapshot();
+
+        // The closure assigns into the environment it shares with the top level
+        run(&mut env, "{ bump(); bump() }")?;
+        run(&mut env, "def extra = println(count)")?;
+        assert_eq!(run(&mut env, "count")?, Expr::Integer(3));
+
+        env.restore(snapshot);
+        assert_eq!(run(&mut env, "count")?, Expr::Integer(1));
+        assert!(run(&mut env, "extra").is_err());
+        assert!(env.global.output.is_empty());
+        // The closure still shares the restored environment
+        run(&mut env, "bump()")?;
+        assert_eq!(run(&mut env, "count")?, Expr::Integer(2));
+
+        // Assignments into the environment of a closure made before the snapshot are undone too
+        run(
+            &mut env,
+            "def make = lambda() { let n = 0; lambda() { n = add(n, 1) } }",
+        )?;
+        run(&mut env, "def counter = make()")?;
+        run(&mut env, "counter()")?;
+        let snapshot = env.snapshot();
+        run(&mut env, "{ counter(); counter() }")?;
+        env.restore(snapshot);
+        assert_eq!(run(&mut env, "counter()")?, Expr::Integer(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_registry() {
+        let builtins = Interpreter::new(true, false, None).builtins();
+        let add = builtins.iter().find(|info| info.name == "add");
+        assert_eq!(
+            add,
+            Some(&BuiltinInfo {
+                name: "add".to_string(),
+                arity: Arity::AtLeast(0),
+                doc: "Sum of integers",
+            })
+        );
+        assert_eq!(Arity::Between(3, 4).to_string(), "3-4");
+
+        // Only the builtins the interpreter was created with are listed, and shadowing one does not remove it
+        let mut minimal = Interpreter::with_builtins(true, false, None, BuiltinSet::minimal());
+        let ast = parser::parse("test", "def add = 1").unwrap();
+        Expr::eval(&ast, &mut minimal).unwrap();
+        let names: Vec<String> = minimal
+            .builtins()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        assert!(names.contains(&"add".to_string()));
+        assert!(!names.contains(&"println".to_string()));
+    }
+
+    #[test]
+    fn builtin_sets() -> Result<(), InterpError> {
+        let ast = parser::parse("test", r#"{ println("hi"); add(1, 2) }"#).unwrap();
+
+        let mut full = Interpreter::with_builtins(true, true, None, BuiltinSet::full());
+        assert_eq!(Expr::eval(&ast, &mut full)?, Expr::Integer(3));
+        assert_eq!(full.global.output, vec!["hi\n"]);
+
+        let mut minimal = Interpreter::with_builtins(true, true, None, BuiltinSet::minimal());
+        assert!(matches!(
+            Expr::eval(&ast, &mut minimal),
+            Err(InterpError::UndefinedError { symbol, .. }) if symbol == "println"
+        ));
+        // Arithmetic and list functions are always available
+        let ast = parser::parse("test", "length(append([1], add(1, 1)))").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut minimal)?, Expr::Integer(2));
+
+        // Groups can be chosen individually
+        let strings = BuiltinSet {
+            strings: true,
+            ..BuiltinSet::minimal()
+        };
+        let mut env = Interpreter::with_builtins(true, true, None, strings);
+        let ast = parser::parse("test", r#"to_uppercase("a")"#).unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::String("A".to_string()));
+        assert!(env.local.borrow().lookup("random").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn let_in_scope() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "{ let y = let x = 2 in mul(x, x); add(y, 1) }").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(5));
+
+        // The binding is only visible in the body, while a plain let lasts for the rest of the block
+        let ast = parser::parse("test", "{ let a = 1 in a; a }").unwrap();
+        assert!(matches!(
+            Expr::eval(&ast, &mut env),
+            Err(InterpError::UndefinedError { symbol, .. }) if symbol == "a"
+        ));
+        let ast = parser::parse("test", "{ let a = 1; a }").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(1));
+
+        // A def in the body stays inside of it
+        let ast = parser::parse("test", "{ let a = 1 in { def b = a }; b }").unwrap();
+        assert!(Expr::eval(&ast, &mut env).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn undefined_identifiers() -> Result<(), InterpError> {
+        let ast = parser::parse("test", "as_list(y, add(1, 2))").unwrap();
+
+        let mut env = Interpreter::new(true, false, None);
+        assert!(matches!(
+            Expr::eval(&ast, &mut env),
+            Err(InterpError::UndefinedError { symbol, .. }) if symbol == "y"
+        ));
+
+        // In the lenient mode the name evaluates to itself, while bound names are still looked up
+        env.global.undefined_as_symbol = true;
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), r#"["y", 3]"#);
+        let ast = parser::parse("test", "{ let y = 1; y }").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut env = Interpreter::new(true, false, None);
+        let nest = |depth: usize, wrap: fn(serde_json::Value) -> serde_json::Value| {
+            (0..depth).fold(serde_json::json!(1), |val, _| wrap(val))
+        };
+        // Built from values directly, json! would copy the nested value by serializing it recursively
+        let list = |val| serde_json::Value::Array(vec![val]);
+        let application = |val| {
+            let mut obj = serde_json::Map::new();
+            obj.insert(
+                "Application".to_string(),
+                serde_json::Value::Array(vec![
+                    serde_json::json!({ "Identifier": "identity" }),
+                    val,
+                ]),
+            );
+            serde_json::Value::Object(obj)
+        };
+
+        // Within the limit both evaluate, an application is an object holding an array
+        assert!(Expr::eval(&nest(100, list), &mut env).is_ok());
+        assert_eq!(
+            Expr::eval(&nest(50, application), &mut env).unwrap(),
+            Expr::Integer(1)
+        );
+
+        for mut ast in [nest(100_000, list), nest(100_000, application)] {
+            assert!(matches!(
+                Expr::eval(&ast, &mut env),
+                Err(InterpError::StackOverflow {
+                    limit: crate::ast::MAX_NESTING
+                })
+            ));
+            // Taken apart one level at a time, dropping the JSON would recurse as deep as it is nested
+            loop {
+                ast = match ast {
+                    serde_json::Value::Array(mut arr) => arr.pop().unwrap_or_default(),
+                    serde_json::Value::Object(mut obj) => {
+                        obj.remove("Application").unwrap_or_default()
+                    }
+                    _ => break,
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn deep_recursion() {
+        // Debug builds use many times the stack per application that release builds do
+        let result = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| {
+                let mut env = Interpreter::new(true, true, None);
+                let run = |env: &mut Interpreter, input: &str| {
+                    let ast = parser::parse("test", input).unwrap();
+                    Expr::eval(&ast, env)
+                };
+                let nest = |depth: usize, wrap: &dyn Fn(String) -> String| {
+                    (0..depth).fold("1".to_string(), |source, _| wrap(source))
+                };
+
+                // Programs nested well within the limits evaluate
+                let adds = nest(70, &|source| format!("add(1, {})", source));
+                assert_eq!(run(&mut env, &adds).unwrap(), Expr::Integer(71));
+                let conds = nest(40, &|source| format!("cond (true => {})", source));
+                assert_eq!(run(&mut env, &conds).unwrap(), Expr::Integer(1));
+                let lets = nest(18, &|source| {
+                    format!("{{ let v = lambda(x) {{ {} }}; v(1) }}", source)
+                });
+                assert_eq!(run(&mut env, &lets).unwrap(), Expr::Integer(1));
+                let count = "{ def count = lambda(n) { cond (zero?(n) => 0) (true => add(1, count(sub(n, 1)))) }; count(250) }";
+                assert_eq!(run(&mut env, count).unwrap(), Expr::Integer(250));
+
+                // Recursion without an end stops at the limit, which try cannot catch
+                let result = run(&mut env, "{ def f = lambda(n) { f(add(n, 1)) }; try { f(0) } catch e { e } }");
+                assert!(matches!(
+                    result,
+                    Err(InterpError::StackOverflow {
+                        limit: Interpreter::MAX_CALL_DEPTH
+                    })
+                ));
+                // The depth is back to zero, so the next program runs normally
+                assert_eq!(env.call_depth, 0);
+                assert_eq!(run(&mut env, "add(1, 2)").unwrap(), Expr::Integer(3));
+            })
+            .unwrap()
+            .join();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn switch_dispatch() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, true, None);
+        let mut eval = |input: &str| {
+            let ast = parser::parse("test", input).unwrap();
+            Expr::eval(&ast, &mut env)
+        };
+        eval(
+            r#"def name = lambda(n) {
+                switch n { (1 => "one") (add(1, 1) => "two") ("3" => "string") (else => "many") }
+            }"#,
+        )?;
+        assert_eq!(eval("name(1)")?, Expr::String("one".to_string()));
+        assert_eq!(eval("name(2)")?, Expr::String("two".to_string()));
+        // Cases are compared by equality, so the integer 3 does not match the string
+        assert_eq!(eval("name(3)")?, Expr::String("many".to_string()));
+        assert_eq!(eval(r#"name("3")"#)?, Expr::String("string".to_string()));
+
+        // Without an else case, no match is false like cond
+        assert_eq!(eval("switch 5 { (1 => 1) }")?, Expr::Boolean(false));
+
+        // The value is evaluated once, and cases only until one matches
+        eval(
+            r#"switch { print("v"); 2 } { ({ print("1"); 1 } => 0) ({ print("2"); 2 } => 0) ({ print("3"); 3 } => 0) }"#,
+        )?;
+        assert_eq!(env.global.output.concat(), "v12");
+
+        Ok(())
+    }
+
+    #[test]
+    fn destructuring_let() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let mut eval = |input: &str| {
+            let ast = parser::parse("test", input).unwrap();
+            Expr::eval(&ast, &mut env)
+        };
+        assert_eq!(
+            eval("{ let divmod = lambda(a, b) { [div(a, b), rem(a, b)] }; let [q, r] = divmod(7, 2); sub(q, r) }")?,
+            Expr::Integer(2)
+        );
+
+        assert!(matches!(
+            eval("let [a, b] = [1, 2, 3]"),
+            Err(InterpError::ValueError { func, .. }) if func == "let"
+        ));
+        assert!(matches!(
+            eval("let [a] = 1"),
+            Err(InterpError::TypeError { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn quote_ast() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "quote(add(1, 2))").unwrap();
+        let quoted = Expr::eval(&ast, &mut env)?;
+        assert!(matches!(quoted, Expr::List(_)));
+        assert_eq!(
+            quoted.to_string(),
+            r#"["Application", [["Identifier", "add"], 1, 2]]"#
+        );
+
+        // Identifiers are not looked up
+        let ast = parser::parse("test", "get(quote(undefined_name), 1)").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?,
+            Expr::String("undefined_name".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_literals() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "cond (true => 1)").unwrap();
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(1));
+
+        // A JSON AST binding the name does not change the boolean
+        let ast = serde_json::json!({ "Block": [
+            { "Let": [{ "Identifier": "true" }, 0] },
+            { "Cond": [{ "Clause": [{ "Identifier": "true" }, 1] }] }
+        ] });
+        assert_eq!(Expr::eval(&ast, &mut env)?, Expr::Integer(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_list_display() {
+        let nested = Expr::List(Rc::new(vec![
+            Expr::Integer(1),
+            Expr::List(Rc::new(vec![Expr::Integer(2), Expr::Integer(3)])),
+            Expr::List(Rc::new(vec![])),
+        ]));
+        assert_eq!(nested.to_string(), "[1, [2, 3], []]");
+    }
+
+    #[test]
+    fn json_null() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        assert_eq!(Expr::eval(&serde_json::Value::Null, &mut env)?, Expr::Nil);
+        let ast = serde_json::json!({ "Application": [{ "Identifier": "as_list" }, null, 1] });
+        assert_eq!(Expr::eval(&ast, &mut env)?.to_string(), "[nil, 1]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_literal() -> Result<(), InterpError> {
+        let mut env = Interpreter::new(true, false, None);
+        let ast = parser::parse("test", "{ let n = 2; [1, n, [add(n, 1)]] }").unwrap();
+        assert_eq!(
+            Expr::eval(&ast, &mut env)?,
+            Expr::List(Rc::new(vec![
+                Expr::Integer(1),
+                Expr::Integer(2),
+                Expr::List(Rc::new(vec![Expr::Integer(3)])),
+            ]))
+        );
+
+        Ok(())
+    }
 }
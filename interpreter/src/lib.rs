@@ -1,14 +1,24 @@
-use error::InterpError;
-use interpreter::{Expr, Interpreter};
+pub use analysis::{analyze, Analysis, AnalysisKind};
+use ast::Ast;
+pub use environment::{Arity, BuiltinInfo, BuiltinSet, OutputHook};
+pub use error::InterpError;
+pub use interpreter::{Expr, Interpreter, Pair, Snapshot, Stats};
+pub use optimize::fold_constants;
 
 // WASM dependencies and functions locked behind "wasm" feature so that the crate does not need to be downloaded on normal runs
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+mod analysis;
+mod ast;
 mod environment;
 mod error;
 mod functions;
 mod interpreter;
+mod optimize;
+mod random;
+#[cfg(all(feature = "parser", not(target_arch = "wasm32")))]
+pub mod repl;
 
 /// Interprets a string, assumed to be valid JSON input from a parser
 /// Returns either the interpreted expression or an error
@@ -33,17 +43,72 @@ pub fn interpret_default(
     lexical_scope: bool,
     store_output: bool,
 ) -> Result<(Expr, Interpreter), InterpError> {
-    let mut env = Interpreter::new(lexical_scope, store_output);
+    // Stored output is limited so that a program printing forever cannot use all memory
+    let output_limit = store_output.then_some(Interpreter::DEFAULT_OUTPUT_LIMIT);
+    let mut env = Interpreter::new(lexical_scope, store_output, output_limit);
     Ok((Expr::eval(&val, &mut env)?, env))
 }
 
+/// Interprets the input JSON with the default environment, for profiling
+/// Returns either an error or a tuple of the resulting expression and the stats of evaluating it
+pub fn interpret_with_stats(
+    val: serde_json::Value,
+    lexical_scope: bool,
+    store_output: bool,
+) -> Result<(Expr, Stats), InterpError> {
+    let (expr, env) = interpret_default(val, lexical_scope, store_output)?;
+    Ok((expr, env.stats))
+}
+
+/// A compiled program, which can be evaluated many times without parsing it again
+pub struct CompiledProgram {
+    ast: Ast,
+}
+
+impl CompiledProgram {
+    /// Compiles a JSON AST produced by a parser
+    pub fn from_value(val: &serde_json::Value) -> Result<Self, InterpError> {
+        Ok(Self {
+            ast: Ast::from_value(val)?,
+        })
+    }
+
+    /// Evaluates the program in the given interpreter
+    /// Use a fresh interpreter for every run that should not see the bindings of previous runs
+    pub fn eval(&self, interpreter: &mut Interpreter) -> Result<Expr, InterpError> {
+        Expr::eval_ast(&self.ast, interpreter)
+    }
+}
+
+/// Parses and compiles a program once, so that it can be evaluated many times
+#[cfg(feature = "parser")]
+#[allow(clippy::result_large_err)]
+pub fn compile(
+    source_name: &str,
+    input: &str,
+) -> Result<CompiledProgram, parser::error::ParseError> {
+    use parser::error::{ParseError, ParseErrorType};
+
+    let ast = parser::parse(source_name, input)?;
+    // The parser only produces valid ASTs, but report a failure against the whole source just in case
+    CompiledProgram::from_value(&ast).map_err(|e| {
+        ParseError::new(
+            ParseErrorType::UNEXPECTED,
+            source_name,
+            input,
+            (0, input.len()),
+            &e.to_string(),
+        )
+    })
+}
+
 /// Interprets a string
 /// Returns the result of interpreting in string form
 /// Expects the string to be valid JSON input
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn interpret_to_string(input: &str, lexical_scope: bool) -> String {
     match interpret_string(input, lexical_scope, true) {
-        Err(e) => return e.to_string(),
+        Err(e) => e.to_string(),
         Ok((expr, env)) => {
             let output = env.global.output.concat();
             output + &expr.to_string()
@@ -58,8 +123,8 @@ pub fn interpret_to_string(input: &str, lexical_scope: bool) -> String {
 pub fn parse_to_string(input: &str) -> String {
     use parser::parse;
     match parse("input", input) {
-        Err(e) => return format!("{:?}", e.as_diagnostic()),
-        Ok(ast) => return serde_json::to_string_pretty(&ast).unwrap(),
+        Err(e) => format!("{:?}", e.as_diagnostic()),
+        Ok(ast) => serde_json::to_string_pretty(&ast).unwrap(),
     }
 }
 
@@ -71,9 +136,9 @@ pub fn parse_to_string(input: &str) -> String {
 pub fn interpret_with_parser_to_string(input: &str, lexical_scope: bool) -> String {
     use parser::parse;
     match parse("input", input) {
-        Err(e) => return format!("{:?}", e.as_diagnostic()),
+        Err(e) => format!("{:?}", e.as_diagnostic()),
         Ok(ast) => match interpret_default(ast, lexical_scope, true) {
-            Err(e) => return e.to_string(),
+            Err(e) => e.to_string(),
             Ok((expr, env)) => {
                 // Output the resulting expression after any stored output
                 let output = env.global.output.concat();
@@ -82,3 +147,76 @@ pub fn interpret_with_parser_to_string(input: &str, lexical_scope: bool) -> Stri
         },
     }
 }
+
+/// Lists the builtins of the default environment
+/// Returns a JSON array of objects with the name, arity (such as "2" or "1+"), and doc string of each builtin
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn builtins_to_string() -> String {
+    let builtins: Vec<serde_json::Value> = Interpreter::new(true, true, None)
+        .builtins()
+        .into_iter()
+        .map(|info| {
+            serde_json::json!({
+                "name": info.name,
+                "arity": info.arity.to_string(),
+                "doc": info.doc,
+            })
+        })
+        .collect();
+    serde_json::Value::Array(builtins).to_string()
+}
+
+// Run with the parser feature (`run.sh -t`)
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_once_eval_twice() -> Result<(), InterpError> {
+        let program = compile(
+            "test",
+            r#"{
+                def count = 0;
+                count = add(count, 1);
+                println("count: ", count);
+                count
+            }"#,
+        )
+        .expect("Test program should compile");
+
+        let mut first = Interpreter::new(true, true, None);
+        let mut second = Interpreter::new(true, true, None);
+        let first_result = program.eval(&mut first)?;
+        let second_result = program.eval(&mut second)?;
+
+        // Each run starts from a fresh environment
+        assert_eq!(first_result, Expr::Integer(1));
+        assert_eq!(first_result, second_result);
+        assert_eq!(first.global.output, second.global.output);
+
+        Ok(())
+    }
+
+    #[test]
+    fn evaluation_stats() -> Result<(), InterpError> {
+        let ast = parser::parse(
+            "test",
+            r#"{
+                def fact = lambda(n) { cond (zero?(n) => 1) (true => mul(n, fact(sub(n, 1)))) };
+                fact(5)
+            }"#,
+        )
+        .expect("Test program should parse");
+        let (result, stats) = interpret_with_stats(ast, true, true)?;
+        assert_eq!(result, Expr::Integer(120));
+
+        // fact is applied 6 times, each (but the last) also applying zero?, mul, and sub
+        assert_eq!(stats.applications, 6 + 6 + 5 + 5);
+        assert!((60..100).contains(&stats.evals), "{} evals", stats.evals);
+        // Arguments are evaluated before applying mul, so only the 6 calls of fact and the last zero? are nested
+        assert_eq!(stats.max_depth, 7);
+
+        Ok(())
+    }
+}
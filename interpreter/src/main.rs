@@ -1,20 +1,126 @@
+/// Options given on the command line
+#[cfg(not(target_arch = "wasm32"))]
+struct Options {
+    // Lexical scope is default, dynamic scope only if '--dynamic-scope' is specified
+    lexical_scope: bool,
+    // Path of a file to read the program from instead of stdin
+    file: Option<String>,
+    // The input is already a JSON AST, so it is not parsed (always the case without the parser feature)
+    ast: bool,
+    // Print the AST as JSON instead of interpreting it
+    #[cfg(feature = "parser")]
+    json_ast: bool,
+    // Start an interactive session instead of reading a whole program
+    #[cfg(feature = "parser")]
+    repl: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Options {
+    const USAGE: &'static str = if cfg!(feature = "parser") {
+        "Usage: interpreter [--lexical-scope | --dynamic-scope] [--file <path>] [--ast | --json-ast | --repl]"
+    } else {
+        "Usage: interpreter [--lexical-scope | --dynamic-scope] [--file <path>] [--ast]"
+    };
+
+    /// Parses the arguments (without the program name), returning an error message for invalid arguments
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            lexical_scope: true,
+            file: None,
+            ast: false,
+            #[cfg(feature = "parser")]
+            json_ast: false,
+            #[cfg(feature = "parser")]
+            repl: false,
+        };
+        let mut scope_flag: Option<String> = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--lexical-scope" | "--dynamic-scope" => {
+                    if let Some(previous) = &scope_flag {
+                        if *previous != arg {
+                            return Err(format!("'{}' cannot be used with '{}'", arg, previous));
+                        }
+                    }
+                    options.lexical_scope = arg == "--lexical-scope";
+                    scope_flag = Some(arg);
+                }
+                "--file" => match args.next() {
+                    Some(_) if options.file.is_some() => return Err("'--file' can only be given once".to_string()),
+                    Some(path) => options.file = Some(path),
+                    None => return Err("'--file' expects a path".to_string()),
+                },
+                "--ast" => options.ast = true,
+                #[cfg(feature = "parser")]
+                "--json-ast" => options.json_ast = true,
+                #[cfg(feature = "parser")]
+                "--repl" => options.repl = true,
+                _ => return Err(format!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        #[cfg(feature = "parser")]
+        if options.ast && (options.json_ast || options.repl) {
+            let other = if options.repl { "--repl" } else { "--json-ast" };
+            return Err(format!("'--ast' cannot be used with '{}'", other));
+        }
+        #[cfg(feature = "parser")]
+        if options.json_ast && options.repl {
+            return Err("'--json-ast' cannot be used with '--repl'".to_string());
+        }
+        #[cfg(feature = "parser")]
+        if options.file.is_some() && options.repl {
+            return Err("'--file' cannot be used with '--repl', use ':load <path>' instead".to_string());
+        }
+
+        Ok(options)
+    }
+
+    /// Name of where the program is read from, used in parse errors
+    #[cfg(feature = "parser")]
+    fn source_name(&self) -> &str {
+        self.file.as_deref().unwrap_or("stdio")
+    }
+
+    /// Reads the whole program from the file if given, else from stdin
+    /// Exits with an error message if it cannot be read
+    fn read_input(&self) -> String {
+        use std::{fs, io};
+
+        let input = match &self.file {
+            Some(path) => fs::read_to_string(path).map_err(|e| format!("Unable to read '{}': {}", path, e)),
+            None => io::read_to_string(io::stdin()).map_err(|e| format!("Error reading from stdin: {}", e)),
+        };
+        input.unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        })
+    }
+}
+
 /// Main function for usage on native tagets with command line
 #[cfg(not(target_arch = "wasm32"))] // Cannot compile code needed std::io on wasm
 pub fn main() {
     use std::env;
 
-    let args: Vec<String> = env::args().collect();
-    // Lexical scope is default, dynamic scope only if argument specified
-    // Like '-- dynamic_scope
-    let lexical_scope = !args.contains(&"dynamic_scope".to_string());
+    // Like '-- --dynamic-scope'
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, Options::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let lexical_scope = options.lexical_scope;
 
     // The normal run without any features, reads in input (expecting JSON) and interprets
     #[cfg(not(feature = "parser"))]
     {
-        use std::io;
         use interpreter::interpret_string;
 
-        let input = io::read_to_string(io::stdin()).expect("Error reading from stdin.");
+        let input = options.read_input();
 
         match interpret_string(&input, lexical_scope, false) {
             Err(e) => {
@@ -35,12 +141,37 @@ pub fn main() {
         use parser::parse;
         use interpreter::interpret_default;
 
-        let input = io::read_to_string(io::stdin());
-        match parse("stdio", &input.expect("Error reading input.")) {
+        if options.repl {
+            let mut repl = interpreter::repl::Repl::new(lexical_scope);
+            if let Err(e) = repl.run(io::stdin().lock(), io::stdout()) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        let input = options.read_input();
+        if options.ast {
+            // The input is a JSON AST, interpreted like in the build without the parser
+            match interpreter::interpret_string(&input, lexical_scope, false) {
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                Ok(expr) => {
+                    println!("{}", expr.0);
+                }
+            }
+            return;
+        }
+        match parse(options.source_name(), &input) {
             Err(e) => {
                 eprintln!("{:?}", e.as_diagnostic());
                 std::process::exit(1);
             },
+            Ok(ast) if options.json_ast => {
+                println!("{}", serde_json::to_string_pretty(&ast).unwrap());
+            },
             Ok(ast) => {
                 match interpret_default(ast, lexical_scope, false) {
                     Err(e) => {
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use crate::{environment::BuiltinSet, Expr, Interpreter};
+
+/// Builtins without side effects, whose result only depends on their arguments
+pub(crate) const PURE_BUILTINS: &[&str] = &[
+    "add", "sub", "mul", "div", "rem", "mod", "gcd", "lcm", "bitand", "bitor", "bitxor", "shl",
+    "shr", "zero?", "not", "and", "or", "equal?", "greater?", "less?", "concat", "format",
+];
+
+/// Replaces applications of pure builtins to literal arguments in a JSON AST with their result
+/// Applications which would error (such as dividing by zero) are left to fail when the program runs
+/// Builtins the program could rebind (any use of their name other than calling it) are not folded,
+/// and nothing is folded in programs using eval, which could rebind them from a string
+pub fn fold_constants(ast: &Value) -> Value {
+    let (mut rebindable, mut called) = (HashSet::new(), HashSet::new());
+    identifiers(ast, &mut rebindable, &mut called);
+    if rebindable.contains("eval") || called.contains("eval") {
+        return ast.clone();
+    }
+
+    let foldable: HashSet<&str> = PURE_BUILTINS
+        .iter()
+        .copied()
+        .filter(|name| !rebindable.contains(*name))
+        .collect();
+    let mut folder = Folder {
+        foldable,
+        interpreter: Interpreter::with_builtins(
+            true,
+            true,
+            Some(0),
+            BuiltinSet {
+                strings: true,
+                ..BuiltinSet::minimal()
+            },
+        ),
+    };
+    folder.fold(ast)
+}
+
+/// Collects identifiers used as the function of an application (called), and those used anywhere else (names)
+fn identifiers(ast: &Value, names: &mut HashSet<String>, called: &mut HashSet<String>) {
+    match ast {
+        Value::Array(arr) => arr.iter().for_each(|ast| identifiers(ast, names, called)),
+        Value::Object(obj) => {
+            for (key, val) in obj {
+                match (key.as_str(), val) {
+                    ("Identifier", Value::String(name)) => {
+                        names.insert(name.clone());
+                    }
+                    ("Application", Value::Array(arr)) => {
+                        // A called identifier is only looked up, anything else is visited as usual
+                        let args = match arr.first().and_then(|func| func.get("Identifier")) {
+                            Some(Value::String(name)) => {
+                                called.insert(name.clone());
+                                &arr[1..]
+                            }
+                            _ => &arr[..],
+                        };
+                        args.iter().for_each(|ast| identifiers(ast, names, called));
+                    }
+                    _ => identifiers(val, names, called),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Folder<'a> {
+    foldable: HashSet<&'a str>,
+    // Evaluates the folded applications, with only the core and string builtins
+    interpreter: Interpreter,
+}
+
+impl Folder<'_> {
+    /// Folds the children of an expression first, so nested applications fold completely
+    fn fold(&mut self, ast: &Value) -> Value {
+        match ast {
+            Value::Array(arr) => Value::Array(arr.iter().map(|ast| self.fold(ast)).collect()),
+            // A quoted AST is data, folding it would change the value
+            Value::Object(obj) if obj.contains_key("Quote") => ast.clone(),
+            Value::Object(obj) => {
+                let obj: Map<String, Value> = obj
+                    .iter()
+                    .map(|(key, val)| (key.clone(), self.fold(val)))
+                    .collect();
+                match obj.get("Application") {
+                    Some(Value::Array(arr)) => self.fold_application(arr),
+                    _ => None,
+                }
+                .unwrap_or(Value::Object(obj))
+            }
+            _ => ast.clone(),
+        }
+    }
+
+    /// Result of an application as a literal, if it can be folded
+    fn fold_application(&mut self, arr: &[Value]) -> Option<Value> {
+        let (func, args) = arr.split_first()?;
+        let name = func.get("Identifier")?.as_str()?;
+        if !self.foldable.contains(name) || !args.iter().all(is_literal) {
+            return None;
+        }
+
+        let application = Value::Object(Map::from_iter([(
+            "Application".to_string(),
+            Value::Array(arr.to_vec()),
+        )]));
+        match Expr::eval(&application, &mut self.interpreter).ok()? {
+            Expr::Integer(int) => Some(Value::from(int)),
+            Expr::Boolean(bool) => Some(Value::Bool(bool)),
+            Expr::String(string) => Some(Value::String(string)),
+            _ => None,
+        }
+    }
+}
+
+/// Integers, booleans, and strings, JSON null and floats are left alone
+fn is_literal(ast: &Value) -> bool {
+    match ast {
+        Value::Number(num) => num.is_i64(),
+        Value::Bool(_) | Value::String(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(input: &str) -> Value {
+        let ast = parser::parse("test", input).expect("Test program should parse");
+        fold_constants(&ast)
+    }
+
+    fn parse(input: &str) -> Value {
+        parser::parse("test", input).expect("Test program should parse")
+    }
+
+    #[test]
+    fn fold_literals() {
+        assert_eq!(fold("add(2, 3)"), Value::from(5));
+        assert_eq!(fold("mul(add(1, 1), sub(10, 4))"), Value::from(12));
+        assert_eq!(fold("less?(1, 2)"), Value::Bool(true));
+        assert_eq!(
+            fold("{ let x = 1; add(x, add(1, 2)) }"),
+            parse("{ let x = 1; add(x, 3) }")
+        );
+    }
+
+    #[test]
+    fn leave_unfoldable() {
+        for input in [
+            "add(x, 3)",
+            "div(1, 0)",
+            "add(9223372036854775807, 1)",
+            "println(1)",
+            "quote(add(1, 2))",
+            // A builtin could be rebound, directly or through eval
+            "{ def add = lambda(a, b) { a }; add(2, 3) }",
+            "{ eval(\"1\"); add(2, 3) }",
+        ] {
+            assert_eq!(fold(input), parse(input), "{} should not be folded", input);
+        }
+    }
+}
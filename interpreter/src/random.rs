@@ -0,0 +1,58 @@
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use wasm_bindgen::prelude::*;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Math, js_name = random)]
+    fn math_random() -> f64;
+}
+
+/// Small pseudo random number generator (SplitMix64)
+/// Not suitable for anything security related, but fast and reproducible from a seed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Seeds from the standard library's random hasher keys
+    /// Those keys are fixed on wasm32-unknown-unknown, so the wasm build seeds from JS's Math.random instead
+    pub fn from_entropy() -> Self {
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+        {
+            // Each call gives at least 32 random bits
+            let high = (math_random() * 4294967296.0) as u64;
+            let low = (math_random() * 4294967296.0) as u64;
+            Self::from_seed(high << 32 | low)
+        }
+        #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+        Self::from_seed(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random integer in the range [low, high), expects low < high
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        let span = (high as i128 - low as i128) as u128;
+        // Scale the random value into the span, avoids the bias of taking a modulo
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        (low as i128 + offset as i128) as i64
+    }
+}
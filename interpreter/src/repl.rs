@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use crate::{Expr, Interpreter};
+
+/// A line of REPL input, either a meta-command starting with ':' or code
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // Evaluate the program in a file into the environment
+    Load(String),
+    // List the bindings made in the session
+    Env,
+    // Exit the REPL
+    Quit,
+    // Program to evaluate
+    Code(String),
+    // Blank line, nothing to do
+    Empty,
+}
+
+impl Command {
+    /// Parses a line of input, returning an error message for unknown or malformed meta-commands
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let Some(meta) = line.strip_prefix(':') else {
+            if line.is_empty() {
+                return Ok(Command::Empty);
+            }
+            return Ok(Command::Code(line.to_string()));
+        };
+
+        let (name, arg) = match meta.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (meta, ""),
+        };
+        match (name, arg) {
+            ("load", "") => Err("Usage: :load <path>".to_string()),
+            ("load", path) => Ok(Command::Load(path.to_string())),
+            ("env", "") => Ok(Command::Env),
+            ("quit", "") | ("q", "") => Ok(Command::Quit),
+            ("env" | "quit" | "q", _) => Err(format!("':{}' does not take an argument", name)),
+            _ => Err(format!(
+                "Unknown command ':{}', expected ':load <path>', ':env', or ':quit'",
+                name
+            )),
+        }
+    }
+}
+
+/// Interactive session, where bindings persist between inputs
+pub struct Repl {
+    interpreter: Interpreter,
+}
+
+impl Repl {
+    pub fn new(lexical_scope: bool) -> Self {
+        Self {
+            interpreter: Interpreter::new(lexical_scope, false, None),
+        }
+    }
+
+    /// Reads lines from the input until it ends or ':quit', writing results and errors to the output
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let response = match Command::parse(&line?) {
+                Ok(Command::Quit) => break,
+                Ok(command) => self.execute(command),
+                Err(message) => Some(message),
+            };
+            if let Some(response) = response {
+                writeln!(output, "{}", response)?;
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    /// Runs a command, returning the text to show for it
+    fn execute(&mut self, command: Command) -> Option<String> {
+        match command {
+            Command::Load(path) => match fs::read_to_string(&path) {
+                Ok(source) => Some(self.evaluate(&path, &source)),
+                Err(e) => Some(format!("Unable to read '{}': {}", path, e)),
+            },
+            Command::Env => {
+                let local = self.interpreter.local.borrow();
+                let bindings: Vec<String> = local
+                    .session_names()
+                    .into_iter()
+                    .map(|name| match local.lookup(&name) {
+                        Some(value) => format!("{} = {}", name, value.display_escaped()),
+                        None => name,
+                    })
+                    .collect();
+                Some(bindings.join("\n")).filter(|bindings| !bindings.is_empty())
+            }
+            Command::Code(code) => Some(self.evaluate("repl", &code)),
+            Command::Quit | Command::Empty => None,
+        }
+    }
+
+    /// Parses and evaluates source in the persistent environment
+    fn evaluate(&mut self, source_name: &str, source: &str) -> String {
+        match parser::parse(source_name, source) {
+            Err(e) => format!("{:?}", e.as_diagnostic()),
+            Ok(ast) => match Expr::eval(&ast, &mut self.interpreter) {
+                Ok(expr) => expr.to_string(),
+                Err(e) => e.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            Command::parse(":load examples/fact.417"),
+            Ok(Command::Load("examples/fact.417".to_string()))
+        );
+        assert_eq!(Command::parse("  :env "), Ok(Command::Env));
+        assert_eq!(Command::parse(":quit"), Ok(Command::Quit));
+        assert_eq!(Command::parse(":q"), Ok(Command::Quit));
+        assert_eq!(Command::parse(""), Ok(Command::Empty));
+
+        // Anything not starting with ':' is code, even if it contains one
+        assert_eq!(
+            Command::parse(r#"println(":env")"#),
+            Ok(Command::Code(r#"println(":env")"#.to_string()))
+        );
+
+        assert!(Command::parse(":load").is_err());
+        assert!(Command::parse(":env x").is_err());
+        assert!(Command::parse(":run").is_err());
+    }
+
+    #[test]
+    fn bindings_persist() {
+        let input = "let x = 5\ndef double = lambda(n) { mul(n, 2) }\ndouble(x)\n:env\n:quit\n1";
+        let mut output = vec![];
+        Repl::new(true).run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("> 10\n"));
+        assert!(output.contains("x = 5"));
+        assert!(output.contains("double = function"));
+        // Nothing is evaluated after quitting
+        assert!(!output.contains("> 1\n"));
+    }
+}
@@ -0,0 +1,137 @@
+//! Tests running the interpreter binary
+//! Run with the parser feature (`run.sh -t`)
+#![cfg(feature = "parser")]
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs the interpreter binary with the arguments, writing the input to stdin
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Interpreter binary should start");
+    // The interpreter may exit before reading stdin (such as on invalid arguments)
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn json_ast_flag() {
+    let output = run(&["--json-ast"], "add(1, 2)");
+    assert!(output.status.success());
+
+    let ast: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        ast,
+        serde_json::json!({ "Application": [{ "Identifier": "add" }, 1, 2] })
+    );
+
+    // Without the flag the program is interpreted
+    let output = run(&[], "add(1, 2)");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "3");
+}
+
+#[test]
+fn ast_flag() {
+    // The input is interpreted as a JSON AST rather than parsed
+    let ast = r#"{ "Application": [{ "Identifier": "mul" }, 6, 7] }"#;
+    let output = run(&["--ast"], ast);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "42");
+
+    // Source code is not a JSON AST
+    let output = run(&["--ast"], "mul(6, 7)");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unable to parse JSON"));
+
+    for args in [&["--ast", "--json-ast"][..], &["--repl", "--ast"]] {
+        let output = run(args, ast);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("'--ast' cannot be used with"));
+    }
+}
+
+#[test]
+fn scope_flags() {
+    // amt is only bound at the time of the call, not when incr is created
+    let program = "{ let incr = lambda(n) { add(amt, n) }; let amt = 1; incr(5) }";
+
+    let output = run(&["--dynamic-scope"], program);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "6");
+
+    for args in [&[][..], &["--lexical-scope"]] {
+        let output = run(args, program);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("amt"));
+    }
+}
+
+#[test]
+fn invalid_flags() {
+    for args in [
+        &["dynamic_scope"][..],
+        &["--unknown"],
+        &["--dynamic-scope", "--lexical-scope"],
+    ] {
+        let output = run(args, "1");
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Usage"));
+    }
+}
+
+#[test]
+fn file_argument() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("file_argument.417");
+    std::fs::write(
+        &path,
+        "{\n    def square = lambda(n) { mul(n, n) };\n    square(7)\n}\n",
+    )
+    .unwrap();
+
+    // Stdin is ignored when reading from a file
+    let output = run(&["--file", path.to_str().unwrap()], "1");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "49");
+
+    let output = run(&["--file", "does/not/exist.417"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unable to read 'does/not/exist.417'"));
+
+    let output = run(&["--file"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage"));
+}
+
+#[test]
+fn print_before_error() {
+    // Both streams go to one file, so it holds the output in the order it was written
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("print_before_error.txt");
+    let file = std::fs::File::create(&path).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .stdin(Stdio::piped())
+        .stdout(file.try_clone().unwrap())
+        .stderr(file)
+        .spawn()
+        .expect("Interpreter binary should start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(br#"add(print("before"), div(1, 0))"#)
+        .unwrap();
+    assert!(!child.wait().unwrap().success());
+
+    let output = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        output.starts_with("beforeArithmetic error in function 'div'"),
+        "{output}"
+    );
+}
@@ -9,7 +9,7 @@ pub enum ParseErrorType {
 }
 
 #[derive(Error, Debug, Diagnostic, Clone)]
-#[error("Error while parsing!")]
+#[error("Error while parsing{}!", .position.map(|(line, column)| format!(" at line {}, column {}", line, column)).unwrap_or_default())]
 pub struct ParseError {
     #[source_code]
     src: NamedSource<String>,
@@ -26,31 +26,70 @@ pub struct ParseError {
 
     #[help]
     help: Option<String>,
+
+    // Line and column where the error starts (both starting at 1), for contexts that do not render the spans
+    position: Option<(usize, usize)>,
+
+    // Further errors found after recovering from this one, reported together
+    #[related]
+    related: Vec<ParseError>,
 }
 
 impl ParseError {
     pub fn new(error_type: ParseErrorType, source_name: &str, src: &str, span: (usize, usize), label: &str) -> Self {
-        let err = Self {
+        Self {
             src: NamedSource::new(source_name, src.to_string()),
             label: label.to_string(),
             error_type,
             main_span: span.into(),
             other_spans: vec![],
-            help: None
-        };
-        err
+            help: None,
+            position: None,
+            related: vec![]
+        }
     }
 
     pub fn new_full(error_type: ParseErrorType, source_name: &str, src: &str, span: (usize, usize), label: &str, help: Option<String>, other_spans: Vec<LabeledSpan>) -> Self {
-        let err = Self {
+        Self {
             src: NamedSource::new(source_name, src.to_string()),
             label: label.to_string(),
             error_type,
             main_span: span.into(),
             other_spans,
-            help
-        };
-        err
+            help,
+            position: None,
+            related: vec![]
+        }
+    }
+
+    pub fn with_position(mut self, line: usize, column: usize) -> Self {
+        self.position = Some((line, column));
+        self
+    }
+
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.position
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Combines errors in the order they were found, the first one holds the rest as related errors
+    pub fn combine(mut errors: Vec<ParseError>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        let mut first = errors.remove(0);
+        first.related.append(&mut errors);
+        Some(first)
+    }
+
+    /// This error followed by all errors related to it
+    pub fn errors(&self) -> Vec<&ParseError> {
+        let mut errors = vec![self];
+        errors.extend(self.related.iter());
+        errors
     }
 
     pub fn change_label(&mut self, new_label: &str) {
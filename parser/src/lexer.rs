@@ -5,23 +5,36 @@ use crate::error::ParseError;
 pub trait LexToken {
     fn token(&self) -> &Token;
     fn source(&self) -> Option<usize>;
+    // Byte offset just after the token
+    fn end(&self) -> Option<usize>;
+    // Line and column of the token, both starting at 1
+    fn position(&self) -> Option<(usize, usize)>;
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Identifier(String),
     String(String),
+    Char(char),
     Keyword(Keyword),
     Integer(i64),
+    // '+' or '-' which is not the sign of an integer
+    Operator(char),
     Equals,
     OpenParen,
     CloseParen,
     OpenBrace,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
     Comma,
     Semicolon,
+    // Separates a parameter from its type
+    Colon,
     Arrow,
-    EOF,
+    // '|>', passes the value on its left to the function on its right
+    Pipe,
+    Eof,
     Error,
 }
 
@@ -33,6 +46,14 @@ impl LexToken for Token {
     fn source(&self) -> Option<usize> {
         None
     }
+
+    fn end(&self) -> Option<usize> {
+        None
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        None
+    }
 }
 
 // Contains a token with additional information
@@ -40,6 +61,9 @@ impl LexToken for Token {
 pub struct TokenContainer {
     pub token: Token,
     pub source: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
 impl LexToken for TokenContainer {
@@ -50,6 +74,14 @@ impl LexToken for TokenContainer {
     fn source(&self) -> Option<usize> {
         Some(self.source)
     }
+
+    fn end(&self) -> Option<usize> {
+        Some(self.end)
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        Some((self.line, self.column))
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -57,7 +89,16 @@ pub enum Keyword {
     Lambda,
     Let,
     Def,
+    Const,
     Cond,
+    Switch,
+    Else,
+    Try,
+    Catch,
+    In,
+    Quote,
+    True,
+    False,
 }
 
 pub struct Lexer<'a> {
@@ -67,7 +108,12 @@ pub struct Lexer<'a> {
     keywords: HashMap<&'a str, Keyword>,
 
     current_location: usize,
+    // Line and column of the next character, both starting at 1
+    current_line: usize,
+    current_column: usize,
     errors: Vec<ParseError>,
+    // Whether the last token ended an operand, after which '+' and '-' are operators rather than signs
+    after_operand: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -77,23 +123,50 @@ impl<'a> Lexer<'a> {
         keywords.insert("λ", Keyword::Lambda);
         keywords.insert("let", Keyword::Let);
         keywords.insert("def", Keyword::Def);
+        keywords.insert("const", Keyword::Const);
         keywords.insert("cond", Keyword::Cond);
-
-        let lexer = Self {
+        keywords.insert("switch", Keyword::Switch);
+        keywords.insert("else", Keyword::Else);
+        keywords.insert("try", Keyword::Try);
+        keywords.insert("catch", Keyword::Catch);
+        keywords.insert("in", Keyword::In);
+        keywords.insert("quote", Keyword::Quote);
+        keywords.insert("true", Keyword::True);
+        keywords.insert("false", Keyword::False);
+
+        let mut lexer = Self {
             source_name,
             source,
             input: source.chars().peekable(),
             keywords,
             current_location: 0,
+            current_line: 1,
+            current_column: 1,
             errors: vec![],
+            after_operand: false,
         };
+
+        // Skip a shebang line (like '#!/usr/bin/env 417lang') so scripts can be executed directly
+        // '#' is not a comment anywhere else
+        if source.starts_with("#!") {
+            lexer.skip_line();
+        }
+
         lexer
     }
 
     // Move to the next character in the input
     fn next_char(&mut self) {
-        self.input.next();
-        self.current_location += 1;
+        if let Some(c) = self.input.next() {
+            // Byte offset, as miette spans index into the source by bytes
+            self.current_location += c.len_utf8();
+            if c == '\n' {
+                self.current_line += 1;
+                self.current_column = 1;
+            } else {
+                self.current_column += 1;
+            }
+        }
     }
 
     // Peek the next character without consuming it
@@ -101,6 +174,19 @@ impl<'a> Lexer<'a> {
         self.input.peek()
     }
 
+    // Whether the next characters are '|>', which ends an identifier like a delimiter would
+    fn at_pipe(&self) -> bool {
+        let mut forward = self.input.clone();
+        forward.next() == Some('|') && forward.next() == Some('>')
+    }
+
+    // Skip characters up to the end of the line or input, leaving the newline
+    fn skip_line(&mut self) {
+        while !matches!(self.peek_char(), Some('\n') | None) {
+            self.next_char();
+        }
+    }
+
     // Skip all whitespace and comments
     fn skip_whitespace_and_comments(&mut self) {
         while let Some(c) = self.peek_char() {
@@ -108,9 +194,7 @@ impl<'a> Lexer<'a> {
                 self.next_char();
             } else if *c == '/' && self.peek_char() == Some(&'/') {
                 // Skip comments until the end of the line
-                while self.peek_char() != Some(&'\n') {
-                    self.next_char();
-                }
+                self.skip_line();
             } else {
                 break;
             }
@@ -118,18 +202,36 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn next_token_container(&mut self) -> TokenContainer {
+        // Skip first so that the source location points at the token itself
+        self.skip_whitespace_and_comments();
+        let (source, line, column) = (
+            self.current_location,
+            self.current_line,
+            self.current_column,
+        );
+        let token = self.next_token();
         TokenContainer {
-            source: self.current_location,
-            token: self.next_token(),
+            token,
+            source,
+            end: self.current_location,
+            line,
+            column,
         }
     }
 
+    // Errors found while lexing so far, leaving none behind
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
     // Lex the next token
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace_and_comments();
 
-        match self.peek_char() {
+        let at_pipe = self.at_pipe();
+        let token = match self.peek_char() {
             Some('"') => self.lex_string(),
+            Some('\'') => self.lex_char(),
             Some('(') => {
                 self.next_char();
                 Token::OpenParen
@@ -146,6 +248,14 @@ impl<'a> Lexer<'a> {
                 self.next_char();
                 Token::CloseBrace
             }
+            Some('[') => {
+                self.next_char();
+                Token::OpenBracket
+            }
+            Some(']') => {
+                self.next_char();
+                Token::CloseBracket
+            }
             Some(',') => {
                 self.next_char();
                 Token::Comma
@@ -154,41 +264,81 @@ impl<'a> Lexer<'a> {
                 self.next_char();
                 Token::Semicolon
             }
+            Some(':') => {
+                self.next_char();
+                Token::Colon
+            }
             Some('=') => {
                 // Make sure we check for '=>' before we try to lex for identifier or keyword as '=' is accepted for that
                 let mut forward = self.input.clone();
                 forward.next();
                 if forward.next() == Some('>') {
                     // Consume both '=' and '>'
-                    self.input = forward;
+                    self.next_char();
+                    self.next_char();
                     Token::Arrow
                 } else {
                     self.next_char();
                     Token::Equals
                 }
             }
+            Some('|') if at_pipe => {
+                // Consume both '|' and '>'
+                self.next_char();
+                self.next_char();
+                Token::Pipe
+            }
             Some(c) if is_id_start(c) => self.lex_identifier_or_keyword(),
-            Some(c) if c.is_digit(10) || *c == '+' || *c == '-' => self.lex_integer(),
+            Some(c) if c.is_ascii_digit() => self.lex_integer(),
+            Some(&c) if c == '+' || c == '-' => {
+                // A sign only where a number can start, so 'x -5' is not 'x' followed by '-5'
+                let mut forward = self.input.clone();
+                forward.next();
+                if !self.after_operand && forward.next().is_some_and(|c| c.is_ascii_digit()) {
+                    self.lex_integer()
+                } else {
+                    self.next_char();
+                    Token::Operator(c)
+                }
+            }
             Some(_) => {
                 let error = ParseError::new(
                     crate::error::ParseErrorType::UNEXPECTED,
-                    &self.source_name,
-                    &self.source,
+                    self.source_name,
+                    self.source,
                     (self.current_location, 1),
                     "Unexpected character",
-                );
+                )
+                .with_position(self.current_line, self.current_column);
                 self.errors.push(error);
                 // advance
                 self.next_char();
                 Token::Error
             }
-            None => Token::EOF,
-        }
+            None => Token::Eof,
+        };
+
+        self.after_operand = matches!(
+            token,
+            Token::Identifier(_)
+                | Token::String(_)
+                | Token::Char(_)
+                | Token::Integer(_)
+                | Token::CloseParen
+                | Token::CloseBrace
+                | Token::CloseBracket
+        );
+        token
     }
 
     // Lex an identifier or a keyword
     fn lex_identifier_or_keyword(&mut self) -> Token {
         let mut identifier = String::new();
+        let (start, line, column) = (
+            self.current_location,
+            self.current_line,
+            self.current_column,
+        );
 
         // First character must be valid IDSTART
         if let Some(c) = self.peek_char() {
@@ -198,25 +348,38 @@ impl<'a> Lexer<'a> {
             } else {
                 let error = ParseError::new(
                     crate::error::ParseErrorType::UNEXPECTED,
-                    &self.source_name,
-                    &self.source,
+                    self.source_name,
+                    self.source,
                     (self.current_location, 1),
                     "Invalid identifier start character",
-                );
+                )
+                .with_position(self.current_line, self.current_column);
                 self.errors.push(error);
             }
         }
 
         // Continue consuming IDCHARs
-        while let Some(c) = self.peek_char() {
-            if is_id_char(c) {
-                identifier.push(*c);
+        while let Some(&c) = self.peek_char() {
+            if is_id_char(&c) && !self.at_pipe() {
+                identifier.push(c);
                 self.next_char();
             } else {
                 break;
             }
         }
 
+        // Only digits and underscores after a leading underscore (like '_1') is a malformed integer, not a name
+        if identifier.starts_with('_')
+            && identifier.contains(|c: char| c.is_ascii_digit())
+            && identifier.chars().all(|c| c == '_' || c.is_ascii_digit())
+        {
+            self.integer_error(
+                "Integer literal cannot start with an underscore",
+                (start, line, column),
+            );
+            return Token::Error;
+        }
+
         match self.keywords.get(identifier.as_str()).cloned() {
             None => Token::Identifier(identifier),
             Some(keyword) => Token::Keyword(keyword.clone()),
@@ -226,23 +389,61 @@ impl<'a> Lexer<'a> {
     // Lex an integer (positive or negative)
     fn lex_integer(&mut self) -> Token {
         let mut num_str = String::new();
+        let (start, line, column) = (
+            self.current_location,
+            self.current_line,
+            self.current_column,
+        );
 
         if self.peek_char() == Some(&'+') || self.peek_char() == Some(&'-') {
             num_str.push(*self.peek_char().unwrap());
             self.next_char();
         }
 
-        while let Some(c) = self.peek_char() {
-            if c.is_digit(10) {
-                num_str.push(*c);
+        // Underscores can separate digits (1_000), but only one at a time and not at the end
+        let mut misplaced_underscore = false;
+        while let Some(&c) = self.peek_char() {
+            if c.is_ascii_digit() {
+                num_str.push(c);
                 self.next_char();
+            } else if c == '_' {
+                self.next_char();
+                let next_is_digit = self.peek_char().is_some_and(|c| c.is_ascii_digit());
+                misplaced_underscore |= !next_is_digit;
             } else {
                 break;
             }
         }
 
-        let num: i64 = num_str.parse().unwrap();
-        Token::Integer(num)
+        if misplaced_underscore {
+            self.integer_error(
+                "Underscores in an integer literal must be between digits",
+                (start, line, column),
+            );
+            return Token::Error;
+        }
+
+        match num_str.parse() {
+            Ok(num) => Token::Integer(num),
+            Err(_) => {
+                // A sign is only lexed here when digits follow it, so the number is too large for an integer
+                self.integer_error("Integer literal out of range", (start, line, column));
+                Token::Error
+            }
+        }
+    }
+
+    // Report a malformed integer literal from its start up to the current location
+    fn integer_error(&mut self, message: &str, (start, line, column): (usize, usize, usize)) {
+        let error = ParseError::new(
+            crate::error::ParseErrorType::UNEXPECTED,
+            self.source_name,
+            self.source,
+            (start, self.current_location - start),
+            message,
+        )
+        .with_position(line, column);
+        self.errors.push(error);
     }
 
     // Lex a string (handles escape sequences)
@@ -258,25 +459,8 @@ impl<'a> Lexer<'a> {
                 }
                 '\\' => {
                     self.next_char(); // Consume the escape character
-                    if let Some(escaped_char) = self.peek_char() {
-                        match escaped_char {
-                            '\\' => string_content.push('\\'),
-                            '"' => string_content.push('"'),
-                            't' => string_content.push('\t'),
-                            'n' => string_content.push('\n'),
-                            'r' => string_content.push('\r'),
-                            _ => {
-                                let error = ParseError::new(
-                                    crate::error::ParseErrorType::UNEXPECTED,
-                                    &self.source_name,
-                                    &self.source,
-                                    (self.current_location, 1),
-                                    "Invalid escape sequence",
-                                );
-                                self.errors.push(error);
-                            }
-                        }
-                        self.next_char();
+                    if let Some(escaped_char) = self.lex_escape('"') {
+                        string_content.push(escaped_char);
                     }
                 }
                 _ => {
@@ -288,6 +472,80 @@ impl<'a> Lexer<'a> {
 
         Token::String(string_content)
     }
+
+    // Lex the character of an escape sequence, after its backslash
+    // quote is the character which ends the literal, so it can be escaped
+    fn lex_escape(&mut self, quote: char) -> Option<char> {
+        let escaped_char = match *self.peek_char()? {
+            '\\' => Some('\\'),
+            't' => Some('\t'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            c if c == quote => Some(quote),
+            _ => {
+                let error = ParseError::new(
+                    crate::error::ParseErrorType::UNEXPECTED,
+                    self.source_name,
+                    self.source,
+                    (self.current_location, 1),
+                    "Invalid escape sequence",
+                )
+                .with_position(self.current_line, self.current_column);
+                self.errors.push(error);
+                None
+            }
+        };
+        self.next_char();
+        escaped_char
+    }
+
+    // Lex a character literal, a single character or escape sequence between single quotes
+    fn lex_char(&mut self) -> Token {
+        let (start, line, column) = (
+            self.current_location,
+            self.current_line,
+            self.current_column,
+        );
+        let errors = self.errors.len();
+        self.next_char(); // Consume the opening quote
+
+        let c = match self.peek_char() {
+            Some('\\') => {
+                self.next_char(); // Consume the escape character
+                self.lex_escape('\'')
+            }
+            Some('\'') | Some('\n') | None => None,
+            Some(&c) => {
+                self.next_char();
+                Some(c)
+            }
+        };
+        if let (Some(c), Some('\'')) = (c, self.peek_char()) {
+            self.next_char(); // Consume the closing quote
+            return Token::Char(c);
+        }
+
+        // Skip the rest of the literal, so its characters are not lexed as tokens
+        while !matches!(self.peek_char(), Some('\'') | Some('\n') | None) {
+            self.next_char();
+        }
+        if self.peek_char() == Some(&'\'') {
+            self.next_char();
+        }
+        // An invalid escape sequence was already reported
+        if self.errors.len() == errors {
+            let error = ParseError::new(
+                crate::error::ParseErrorType::UNEXPECTED,
+                self.source_name,
+                self.source,
+                (start, self.current_location - start),
+                "A character literal must be a single character between single quotes",
+            )
+            .with_position(line, column);
+            self.errors.push(error);
+        }
+        Token::Error
+    }
 }
 
 // Helper functions
@@ -295,12 +553,7 @@ impl<'a> Lexer<'a> {
 // Check if a character is a valid IDSTART
 fn is_id_start(c: &char) -> bool {
     // IDSTART must be a valid Unicode character, but not a digit, '+' or '-'
-    is_id_char(c)
-        && !(c.is_digit(10)
-            || match c {
-                '+' | '-' => true,
-                _ => false,
-            })
+    is_id_char(c) && !(c.is_ascii_digit() || matches!(c, '+' | '-'))
 }
 
 // Check if a character is a valid IDCHAR
@@ -311,10 +564,10 @@ fn is_id_char(c: &char) -> bool {
 
 // Check if a character is a delimiter
 fn is_delimiter(c: &char) -> bool {
-    match c {
-        ' ' | '\t' | '\n' | '"' | '(' | ')' | '{' | '}' | ',' | ';' => true,
-        _ => false,
-    }
+    matches!(
+        c,
+        ' ' | '\t' | '\n' | '"' | '(' | ')' | '{' | '}' | '[' | ']' | ',' | ';' | ':'
+    )
 }
 
 #[cfg(test)]
@@ -337,9 +590,137 @@ mod tests {
         loop {
             let token = lexer.next_token();
             println!("{:?}", token);
-            if token == Token::EOF {
+            if token == Token::Eof {
                 break;
             }
         }
     }
+
+    #[test]
+    fn line_and_column() {
+        let input = "{\n  a; // comment\n    λ(b) => c\n}";
+        let mut lexer = Lexer::new("test", input);
+
+        let mut tokens = vec![];
+        loop {
+            let container = lexer.next_token_container();
+            if container.token == Token::Eof {
+                break;
+            }
+            tokens.push(container);
+        }
+
+        let positions: Vec<(&Token, usize, usize)> = tokens
+            .iter()
+            .map(|t| (&t.token, t.line, t.column))
+            .collect();
+        assert_eq!(positions[1], (&Token::Identifier("a".to_string()), 2, 3));
+        // First token on the third line, after a comment on the line before it
+        assert_eq!(positions[3], (&Token::Keyword(Keyword::Lambda), 3, 5));
+        // Columns count characters, and '=>' is two of them
+        assert_eq!(positions[7], (&Token::Arrow, 3, 10));
+        assert_eq!(positions[8], (&Token::Identifier("c".to_string()), 3, 13));
+        assert_eq!(positions[9], (&Token::CloseBrace, 4, 1));
+    }
+
+    #[test]
+    fn shebang_line() {
+        let mut lexer = Lexer::new("test", "#!/usr/bin/env 417lang\nadd(1, 2)");
+        let container = lexer.next_token_container();
+        assert_eq!(container.token, Token::Identifier("add".to_string()));
+        assert_eq!((container.line, container.column), (2, 1));
+
+        // Only a shebang, without a newline after it
+        assert_eq!(Lexer::new("test", "#!417lang").next_token(), Token::Eof);
+        // Elsewhere '#' is part of an identifier
+        assert_eq!(
+            Lexer::new("test", " #!a").next_token(),
+            Token::Identifier("#!a".to_string())
+        );
+    }
+
+    #[test]
+    fn integer_separators() {
+        let lex = |source| {
+            let mut lexer = Lexer::new("test", source);
+            (lexer.next_token(), lexer.take_errors().len())
+        };
+
+        assert_eq!(lex("1_000"), (Token::Integer(1000), 0));
+        assert_eq!(lex("-1_000_000"), (Token::Integer(-1_000_000), 0));
+        for malformed in ["1__0", "1_", "_1", "_1_000"] {
+            assert_eq!(lex(malformed), (Token::Error, 1), "{}", malformed);
+        }
+        // Names can still start with an underscore
+        assert_eq!(lex("_x1"), (Token::Identifier("_x1".to_string()), 0));
+    }
+
+    #[test]
+    fn char_literals() {
+        let lex = |source| {
+            let mut lexer = Lexer::new("test", source);
+            (lexer.next_token(), lexer.take_errors().len())
+        };
+
+        assert_eq!(lex("'a'"), (Token::Char('a'), 0));
+        assert_eq!(lex(r"'\n'"), (Token::Char('\n'), 0));
+        assert_eq!(lex(r"'\''"), (Token::Char('\''), 0));
+        assert_eq!(lex("'🦀'"), (Token::Char('🦀'), 0));
+        // Each malformed literal is reported once
+        for malformed in ["''", "'ab'", "'a", r"'\q'"] {
+            assert_eq!(lex(malformed), (Token::Error, 1), "{}", malformed);
+        }
+        // The rest of a malformed literal is skipped
+        let mut lexer = Lexer::new("test", "'ab' c");
+        lexer.next_token();
+        assert_eq!(lexer.next_token(), Token::Identifier("c".to_string()));
+    }
+
+    #[test]
+    fn sign_or_operator() {
+        let tokens = |source| {
+            let mut lexer = Lexer::new("test", source);
+            let mut tokens = vec![];
+            loop {
+                match lexer.next_token() {
+                    Token::Eof => return tokens,
+                    token => tokens.push(token),
+                }
+            }
+        };
+
+        assert_eq!(tokens("-5"), vec![Token::Integer(-5)]);
+        assert_eq!(
+            tokens("f(-5, +2)"),
+            vec![
+                Token::Identifier("f".to_string()),
+                Token::OpenParen,
+                Token::Integer(-5),
+                Token::Comma,
+                Token::Integer(2),
+                Token::CloseParen,
+            ]
+        );
+        // After an operand the sign is an operator, with or without a space
+        let subtraction = vec![
+            Token::Identifier("x".to_string()),
+            Token::Operator('-'),
+            Token::Integer(5),
+        ];
+        assert_eq!(tokens("x - 5"), subtraction);
+        assert_eq!(tokens("x -5"), subtraction);
+        assert_eq!(
+            tokens("f(1) +x"),
+            vec![
+                Token::Identifier("f".to_string()),
+                Token::OpenParen,
+                Token::Integer(1),
+                Token::CloseParen,
+                Token::Operator('+'),
+                Token::Identifier("x".to_string()),
+            ]
+        );
+        // Inside of an identifier it is still part of the name
+        assert_eq!(tokens("a-1"), vec![Token::Identifier("a-1".to_string())]);
+    }
 }
@@ -1,3 +1,6 @@
+// ParseError carries its source for miette diagnostics, so it is large by design
+#![allow(clippy::result_large_err)]
+
 use lexer::{Lexer, Token};
 use parser::Parser;
 use serde_json::Value;
@@ -7,17 +10,66 @@ mod lexer;
 mod parser;
 
 pub fn parse(source_name: &str, input: &str) -> Result<Value, error::ParseError> {
+    parse_tokens(source_name, input, false)
+}
+
+/// Parses like parse, also adding a "span" key to every expression object of the AST
+/// The span is the [offset, length] of the expression in the input, in bytes
+pub fn parse_with_spans(source_name: &str, input: &str) -> Result<Value, error::ParseError> {
+    parse_tokens(source_name, input, true)
+}
+
+fn parse_tokens(source_name: &str, input: &str, spans: bool) -> Result<Value, error::ParseError> {
     let mut lexer = Lexer::new(source_name, input);
 
     let mut tokens = vec![];
     loop {
         let token = lexer.next_token_container();
-        if token.token == Token::EOF {
+        if token.token == Token::Eof {
             tokens.push(token);
             break;
         }
         tokens.push(token);
     }
 
-    Parser::new(source_name, input, &tokens).parse_program()
-}
\ No newline at end of file
+    // Do not parse tokens from invalid input, report every lexer error instead
+    if let Some(error) = error::ParseError::combine(lexer.take_errors()) {
+        return Err(error);
+    }
+
+    Parser::new(source_name, input, &tokens)
+        .with_spans(spans)
+        .parse_program()
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::Diagnostic;
+
+    use super::*;
+
+    #[test]
+    fn lexer_errors() {
+        // Invalid escape sequence and an integer out of range
+        let err = parse("test", r#"{ println("a\qb"); add(1, -99999999999999999999) }"#).unwrap_err();
+        let errors = err.errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].position(), Some((1, 14)));
+        assert_eq!(errors[1].position(), Some((1, 27)));
+
+        // A sign without digits is an operator, which is a parser error instead
+        assert!(parse("test", "add(1, -)").is_err());
+    }
+
+    #[test]
+    fn lexer_error_source_name() {
+        let err = parse("example.417", r#""\q""#).unwrap_err();
+        let label = err.labels().unwrap().next().unwrap();
+        let contents = err
+            .source_code()
+            .unwrap()
+            .read_span(label.inner(), 0, 0)
+            .unwrap();
+        assert_eq!(contents.name(), Some("example.417"));
+    }
+}
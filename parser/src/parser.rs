@@ -10,6 +10,10 @@ use crate::{
 pub struct Parser<'a, T: LexToken> {
     tokens: &'a [T],
     current: usize,
+    // Errors recovered from so far, reported together at the end
+    errors: Vec<ParseError>,
+    // Whether expression objects get a "span" key with their source location
+    spans: bool,
 
     source_name: &'a str,
     source: &'a str,
@@ -20,20 +24,57 @@ impl<'a, T: LexToken> Parser<'a, T> {
         Parser {
             tokens,
             current: 0,
+            errors: vec![],
+            spans: false,
             source_name,
             source,
         }
     }
 
+    // Whether to add a "span" key to every expression object, holding its byte offset and length in the source
+    // Literals are plain JSON values, so they have no span
+    pub fn with_spans(mut self, spans: bool) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    // Adds the span from start to the end of the last consumed token to an object
+    fn spanned(&self, expr: Value, start: Option<usize>) -> Value {
+        let end = self
+            .current
+            .checked_sub(1)
+            .and_then(|i| self.tokens[i].end());
+        match (expr, start, end) {
+            (Value::Object(mut obj), Some(start), Some(end)) if self.spans => {
+                obj.insert("span".to_string(), json!([start, end - start]));
+                Value::Object(obj)
+            }
+            (expr, _, _) => expr,
+        }
+    }
+
     // Utility function to get the current token
     fn current_token(&self) -> &Token {
         self.tokens[self.current].token()
     }
 
+    // Utility function to get the token after the current one
+    fn peek_token(&self) -> Option<&Token> {
+        self.tokens.get(self.current + 1).map(|token| token.token())
+    }
+
     fn current_source(&self) -> Option<usize> {
         self.tokens[self.current].source()
     }
 
+    // Adds the line and column of the current token to an error
+    fn at_current(&self, error: ParseError) -> ParseError {
+        match self.tokens[self.current].position() {
+            Some((line, column)) => error.with_position(line, column),
+            None => error,
+        }
+    }
+
     // Utility function to move to the next token
     fn next_token(&mut self) {
         self.current += 1;
@@ -49,56 +90,156 @@ impl<'a, T: LexToken> Parser<'a, T> {
         }
     }
 
-    // Entry point for parsing a program (EXP := FORM | ATOM)
+    // Entry point for parsing a program (PROGRAM := EXP?)
+    // The whole input must be consumed by the expression, an input with no expression is the nil program (JSON null)
+    // All errors found are returned together, the first error holding the others
     pub fn parse_program(&mut self) -> Result<Value, ParseError> {
-        self.parse_exp()
+        if self.current_token() == &Token::Eof {
+            return Ok(Value::Null);
+        }
+        let program = self.parse_exp().and_then(|program| {
+            if self.current_token() != &Token::Eof {
+                return Err(self.at_current(ParseError::new_full(
+                    crate::error::ParseErrorType::UNEXPECTED,
+                    self.source_name,
+                    self.source,
+                    (self.current_source().unwrap(), 1),
+                    "Unexpected token after the end of the program",
+                    Some("A program is a single expression, place multiple expressions inside of a block '{ }' separated by ';'".to_string()),
+                    vec![],
+                )));
+            }
+            Ok(program)
+        });
+
+        match program {
+            Ok(program) if self.errors.is_empty() => Ok(program),
+            Ok(_) => Err(ParseError::combine(std::mem::take(&mut self.errors)).unwrap()),
+            Err(e) => {
+                self.errors.push(e);
+                Err(ParseError::combine(std::mem::take(&mut self.errors)).unwrap())
+            }
+        }
     }
 
-    // EXP := FORM | ATOM
+    // Skips tokens after an error up to the end of the current expression in a block
+    // Stops after a ';' or before a '}', so that the block can continue with its next expression
+    fn synchronize(&mut self) {
+        loop {
+            match self.current_token() {
+                Token::Semicolon => {
+                    self.next_token();
+                    return;
+                }
+                Token::CloseBrace | Token::Eof => return,
+                _ => self.next_token(),
+            }
+        }
+    }
+
+    // EXP := OPERAND ('|>' OPERAND)*
+    // Pipes are left associative and lowered to applications, so 'x |> f |> g(1)' is 'g(f(x), 1)'
     fn parse_exp(&mut self) -> Result<Value, ParseError> {
+        let start = self.current_source();
+        let mut expr = self.parse_operand()?;
+        while self.consume(&Token::Pipe) {
+            expr = match self.parse_operand()? {
+                // The piped value becomes the first argument of an application
+                Value::Object(mut obj) if obj.contains_key("Application") => {
+                    if let Some(Value::Array(args)) = obj.get_mut("Application") {
+                        args.insert(1, expr);
+                    }
+                    Value::Object(obj)
+                }
+                func => json!({ "Application": [func, expr] }),
+            };
+            // A lowered pipe spans from the start of the value piped into it
+            expr = self.spanned(expr, start);
+        }
+        Ok(expr)
+    }
+
+    // OPERAND := FORM | ATOM | LIST
+    fn parse_operand(&mut self) -> Result<Value, ParseError> {
+        let start = self.current_source();
         let expr = match self.current_token() {
-            Token::Identifier(_) | Token::Integer(_) | Token::String(_) => self.parse_atom(),
+            Token::Identifier(_) | Token::Integer(_) | Token::String(_) | Token::Char(_) => {
+                self.parse_atom()
+            }
             Token::Keyword(_) => self.parse_form(),
             Token::OpenBrace => self.parse_block(),
+            Token::OpenBracket => self.parse_list(),
+            Token::Operator(_) => {
+                return Err(self.at_current(ParseError::new_full(
+                    crate::error::ParseErrorType::UNEXPECTED,
+                    self.source_name,
+                    self.source,
+                    (self.current_source().unwrap(), 1),
+                    "Unexpected operator",
+                    Some("There are no infix operators, use functions like 'sub(a, b)'. A sign is only part of a number at the start of an expression, so separate arguments with ','".to_string()),
+                    vec![],
+                )));
+            }
             _ => {
-                let err = ParseError::new_full(
+                let err = self.at_current(ParseError::new_full(
                     crate::error::ParseErrorType::UNEXPECTED,
-                    &self.source_name,
-                    &self.source,
-                    (self.current_source().unwrap(), 1).into(),
+                    self.source_name,
+                    self.source,
+                    (self.current_source().unwrap(), 1),
                     "Expected expression",
                     Some("You probably forgot to close either a block or function application, try placing a '}' or ')' if appropriate.".to_string()),
                     vec![]
-                );
+                ));
                 return Err(err);
             }
         };
 
         // If the next token is an OpenParen, treat it as a function application
         if let Token::OpenParen = self.current_token() {
-            return self.parse_application(expr?);
+            let application = self.parse_application(self.spanned(expr?, start))?;
+            return Ok(self.spanned(application, start));
         }
 
-        expr
+        Ok(self.spanned(expr?, start))
     }
 
-    // FORM := APPLICATION | LAMBDA | COND | BLOCK | LET | DEFINITION
+    // FORM := APPLICATION | LAMBDA | COND | SWITCH | BLOCK | LET | DEFINITION | CONSTANT | TRY | QUOTE
     fn parse_form(&mut self) -> Result<Value, ParseError> {
         match &self.current_token() {
             Token::Keyword(kw) => match kw {
-                Keyword::Def => self.parse_definition(),
+                Keyword::Def => self.parse_definition(Keyword::Def),
+                Keyword::Const => self.parse_definition(Keyword::Const),
                 Keyword::Let => self.parse_let(),
                 Keyword::Lambda => self.parse_lambda(),
                 Keyword::Cond => self.parse_cond(),
+                Keyword::Switch => self.parse_switch(),
+                Keyword::Try => self.parse_try(),
+                Keyword::Quote => self.parse_quote(),
+                Keyword::True | Keyword::False => self.parse_atom(),
+                Keyword::Catch => Err(self
+                    .misplaced_keyword(5, "'catch' can only follow the block of a try expression")),
+                Keyword::Else => Err(self.misplaced_keyword(
+                    4,
+                    "'else' can only start the last case of a switch expression",
+                )),
+                Keyword::In => {
+                    Err(self
+                        .misplaced_keyword(2, "'in' can only follow the value of a let expression"))
+                }
             },
             _ => panic!("Unexpected form: {:?}", self.current_token()),
         }
     }
 
-    // ATOM := IDENTIFIER | STRING | INTEGER
+    // ATOM := IDENTIFIER | STRING | CHAR | INTEGER | BOOLEAN
     fn parse_atom(&mut self) -> Result<Value, ParseError> {
         match self.current_token().clone() {
             Token::Identifier(_) => self.parse_assignment(),
+            // Booleans are keywords, so they cannot be rebound
+            Token::Keyword(keyword @ (Keyword::True | Keyword::False)) => {
+                self.next_token(); // Consume the boolean
+                Ok(json!(keyword == Keyword::True))
+            }
             Token::Integer(ref num) => {
                 let value = json!(num);
                 self.next_token(); // Consume the integer
@@ -109,6 +250,11 @@ impl<'a, T: LexToken> Parser<'a, T> {
                 self.next_token(); // Consume the string
                 Ok(value)
             }
+            // JSON has no character type, so it is a string tagged as a character
+            Token::Char(c) => {
+                self.next_token(); // Consume the character
+                Ok(json!({ "Char": c.to_string() }))
+            }
             _ => panic!("Unexpected atom: {:?}", self.current_token()),
         }
     }
@@ -123,10 +269,10 @@ impl<'a, T: LexToken> Parser<'a, T> {
         if !self.consume(&Token::CloseParen) {
             loop {
                 args.push(self.parse_exp()?);
+                self.consume(&Token::Comma); // Optional ',' between arguments, or a trailing one
                 if self.consume(&Token::CloseParen) {
                     break;
                 }
-                self.consume(&Token::Comma); // Optional ',' between arguments
             }
         }
 
@@ -134,27 +280,67 @@ impl<'a, T: LexToken> Parser<'a, T> {
         Ok(json!({ "Application": args }))
     }
 
+    // LIST := '[' (EXP (',' EXP)*)? ']'
+    // A JSON array in the AST is evaluated as a list
+    fn parse_list(&mut self) -> Result<Value, ParseError> {
+        self.consume(&Token::OpenBracket); // Expect '['
+        let mut elements = vec![];
+
+        if !self.consume(&Token::CloseBracket) {
+            loop {
+                elements.push(self.parse_exp()?);
+                self.consume(&Token::Comma); // Optional ',' between elements, or a trailing one
+                if self.consume(&Token::CloseBracket) {
+                    break;
+                }
+            }
+        }
+
+        Ok(Value::Array(elements))
+    }
+
     // LAMBDA := ('lambda' | 'λ') '(' PARAMETERS ')' BLOCK
     fn parse_lambda(&mut self) -> Result<Value, ParseError> {
         self.next_token(); // Consume 'lambda' or 'λ'
         self.consume(&Token::OpenParen); // Expect '('
-        let params = self.parse_parameters(); // Parse parameters
+        let params = self.parse_parameters()?; // Parse parameters
         self.consume(&Token::CloseParen); // Expect ')'
         let block = self.parse_block()?; // Parse block
         Ok(json!({ "Lambda": [params, block] }))
     }
 
-    // PARAMETERS := IDENTIFIER (',' IDENTIFIER)*
-    fn parse_parameters(&mut self) -> Value {
+    // PARAMETERS := PARAMETER (',' PARAMETER)* ','?
+    // PARAMETER := IDENTIFIER (':' IDENTIFIER)?
+    // The type name is checked by the interpreter
+    fn parse_parameters(&mut self) -> Result<Value, ParseError> {
         let mut params = vec![];
         while let Token::Identifier(ref name) = self.current_token() {
-            params.push(json!({ "Identifier": name }));
+            let mut param = json!({ "Identifier": name });
             self.next_token(); // Consume the identifier
+            if self.consume(&Token::Colon) {
+                let Token::Identifier(type_name) = self.current_token().clone() else {
+                    return Err(self.at_current(ParseError::new_full(
+                        crate::error::ParseErrorType::UNEXPECTED,
+                        self.source_name,
+                        self.source,
+                        (self.current_source().unwrap(), 1),
+                        "Expected a type name",
+                        Some(
+                            "An annotated parameter has form 'name: type', such as 'n: integer'"
+                                .to_string(),
+                        ),
+                        vec![],
+                    )));
+                };
+                param["Type"] = json!(type_name);
+                self.next_token(); // Consume the type name
+            }
+            params.push(param);
             if !self.consume(&Token::Comma) {
                 break;
             }
         }
-        json!({ "Parameters": params })
+        Ok(json!({ "Parameters": params }))
     }
 
     // COND := 'cond' CLAUSE+
@@ -167,6 +353,98 @@ impl<'a, T: LexToken> Parser<'a, T> {
         Ok(json!({ "Cond": clauses }))
     }
 
+    // SWITCH := 'switch' EXP '{' CLAUSE* ('(' 'else' '=>' EXP ')')? '}'
+    // The value is compared to the first expression of each clause, the else case is used when none are equal
+    fn parse_switch(&mut self) -> Result<Value, ParseError> {
+        self.next_token(); // Consume 'switch'
+        let value = self.parse_exp()?;
+        if !self.consume(&Token::OpenBrace) {
+            return Err(self.switch_error("Expected a '{'"));
+        }
+        let mut clauses = vec![];
+        let mut default = None;
+        while !self.consume(&Token::CloseBrace) {
+            if default.is_some() || self.current_token() != &Token::OpenParen {
+                return Err(self.switch_error("Expected a case or '}'"));
+            }
+            if self.peek_token() == Some(&Token::Keyword(Keyword::Else)) {
+                self.next_token(); // Consume '('
+                self.next_token(); // Consume 'else'
+                self.consume(&Token::Arrow); // Expect '=>'
+                default = Some(self.parse_exp()?);
+                self.consume(&Token::CloseParen); // Expect ')'
+            } else {
+                clauses.push(self.parse_clause()?);
+            }
+        }
+        match default {
+            Some(default) => Ok(json!({ "Switch": [value, clauses, default] })),
+            None => Ok(json!({ "Switch": [value, clauses] })),
+        }
+    }
+
+    /// Error at the current token in a switch expression
+    fn switch_error(&self, label: &str) -> ParseError {
+        self.at_current(ParseError::new_full(
+            crate::error::ParseErrorType::UNEXPECTED,
+            self.source_name,
+            self.source,
+            (self.current_source().unwrap(), 1),
+            label,
+            Some(
+                "Switch expression has form 'switch x { (1 => a) (2 => b) (else => c) }', with else last"
+                    .to_string(),
+            ),
+            vec![],
+        ))
+    }
+
+    // TRY := 'try' BLOCK 'catch' IDENTIFIER BLOCK
+    fn parse_try(&mut self) -> Result<Value, ParseError> {
+        self.next_token(); // Consume 'try'
+        let body = self.parse_block()?;
+        if !self.consume(&Token::Keyword(Keyword::Catch)) {
+            return Err(self.at_current(ParseError::new_full(
+                crate::error::ParseErrorType::UNEXPECTED,
+                self.source_name,
+                self.source,
+                (self.current_source().unwrap(), 1),
+                "Expected 'catch'",
+                Some("Try expression has form 'try { ... } catch e { ... }'".to_string()),
+                vec![],
+            )));
+        }
+        let name = self.parse_identifier()?;
+        let handler = self.parse_block()?;
+        Ok(json!({ "Try": [body, name, handler] }))
+    }
+
+    // QUOTE := 'quote' '(' EXP ')'
+    // The expression is not evaluated, its AST is the value
+    fn parse_quote(&mut self) -> Result<Value, ParseError> {
+        self.next_token(); // Consume 'quote'
+        if !self.consume(&Token::OpenParen) {
+            return Err(self.quote_error("Expected '('"));
+        }
+        let exp = self.parse_exp()?;
+        if !self.consume(&Token::CloseParen) {
+            return Err(self.quote_error("Expected ')'"));
+        }
+        Ok(json!({ "Quote": exp }))
+    }
+
+    fn quote_error(&self, label: &str) -> ParseError {
+        self.at_current(ParseError::new_full(
+            crate::error::ParseErrorType::UNEXPECTED,
+            self.source_name,
+            self.source,
+            (self.current_source().unwrap(), 1),
+            label,
+            Some("Quote expression has form 'quote(exp)'".to_string()),
+            vec![],
+        ))
+    }
+
     // CLAUSE := '(' EXP '=>' EXP ')'
     fn parse_clause(&mut self) -> Result<Value, ParseError> {
         self.consume(&Token::OpenParen); // Expect '('
@@ -182,15 +460,15 @@ impl<'a, T: LexToken> Parser<'a, T> {
         let current_source = self.current_source(); // used to construct error if needed
         if !self.consume(&Token::OpenBrace) {
             // Expect '{'
-            return Err(ParseError::new_full(
+            return Err(self.at_current(ParseError::new_full(
                 crate::error::ParseErrorType::BLOCK,
-                &self.source_name,
-                &self.source,
-                (current_source.unwrap(), 1).into(),
+                self.source_name,
+                self.source,
+                (current_source.unwrap(), 1),
                 "Expected a block",
                 Some("Create a block with enclosing braces".to_string()),
                 vec![],
-            ));
+            )));
         }
         let mut exps = vec![];
         while !self.consume(&Token::CloseBrace) {
@@ -198,20 +476,24 @@ impl<'a, T: LexToken> Parser<'a, T> {
             match self.parse_exp() {
                 Ok(exp) => exps.push(exp),
                 Err(mut e) => {
-                    match e.error_type {
-                        ParseErrorType::BLOCK => {
-                            // customize error message
-                            e.change_label("Found end of block");
-                            let start_block_span = LabeledSpan::at(
-                                current_source.expect("Expect block start source to exist"),
-                                "Found opening '{' here",
-                            );
-                            e.add_spans(&mut vec![start_block_span]);
-                            e.add_help("Close the block with a '}'");
-                        }
-                        _ => {}
+                    if let ParseErrorType::BLOCK = e.error_type {
+                        // customize error message
+                        e.change_label("Found end of block");
+                        let start_block_span = LabeledSpan::at(
+                            current_source.expect("Expect block start source to exist"),
+                            "Found opening '{' here",
+                        );
+                        e.add_spans(&mut vec![start_block_span]);
+                        e.add_help("Close the block with a '}'");
+                        return Err(e);
                     }
-                    return Err(e);
+                    // Nothing is left to recover with at the end of the input
+                    if self.current_token() == &Token::Eof {
+                        return Err(e);
+                    }
+                    // Keep parsing the rest of the block to find any further errors
+                    self.errors.push(e);
+                    self.synchronize();
                 }
             }
             if self.consume(&Token::Semicolon) {
@@ -221,44 +503,92 @@ impl<'a, T: LexToken> Parser<'a, T> {
         Ok(json!({ "Block": exps }))
     }
 
-    // LET := 'let' IDENTIFIER '=' EXP
+    /// Error for a keyword that only has a meaning inside of another form, length is the keyword's length
+    fn misplaced_keyword(&self, length: usize, help: &str) -> ParseError {
+        self.at_current(ParseError::new_full(
+            crate::error::ParseErrorType::UNEXPECTED,
+            self.source_name,
+            self.source,
+            (self.current_source().unwrap(), length),
+            "Expected expression",
+            Some(help.to_string()),
+            vec![],
+        ))
+    }
+
+    // LET := 'let' (IDENTIFIER | PATTERN) '=' EXP ('in' EXP)?
+    // PATTERN := '[' IDENTIFIER (',' IDENTIFIER)* ','? ']'
+    // The value can be any expression, including a block: 'let x = { a; b }' binds the value of 'b'
+    // Without 'in' the binding lasts for the rest of the enclosing block, with it only for the body after 'in'
+    // A pattern binds each identifier to the element of the list value at the same position, and cannot have 'in'
     fn parse_let(&mut self) -> Result<Value, ParseError> {
         self.consume(&Token::Keyword(Keyword::Let)); // Expect 'let'
-        let identifier = self.parse_identifier()?;
+        let is_pattern = matches!(self.current_token(), Token::OpenBracket);
+        let identifier = if is_pattern {
+            self.parse_pattern()?
+        } else {
+            self.parse_identifier()?
+        };
         if !self.consume(&Token::Equals) {
             // Expect '='
-            return Err(ParseError::new_full(
+            return Err(self.at_current(ParseError::new_full(
                 crate::error::ParseErrorType::LET,
-                &self.source_name,
-                &self.source,
-                (self.current_source().unwrap() + 1, 1).into(),
+                self.source_name,
+                self.source,
+                (self.current_source().unwrap(), 1),
                 "Expected an '='",
                 Some("Let expression has form 'let x = 5'".to_string()),
                 vec![],
-            ));
+            )));
         }
         let exp = self.parse_exp()?;
+        if is_pattern {
+            return Ok(json!({ "Destructure": [identifier, exp] }));
+        }
+        if self.consume(&Token::Keyword(Keyword::In)) {
+            let body = self.parse_exp()?;
+            return Ok(json!({ "LetIn": [identifier, exp, body] }));
+        }
         Ok(json!({ "Let": [identifier, exp] }))
     }
 
-    // DEFINITION := 'def' IDENTIFIER EXP
-    fn parse_definition(&mut self) -> Result<Value, ParseError> {
-        self.consume(&Token::Keyword(Keyword::Def));
+    // DEFINITION := 'def' IDENTIFIER '=' EXP
+    // CONSTANT := 'const' IDENTIFIER '=' EXP
+    fn parse_definition(&mut self, keyword: Keyword) -> Result<Value, ParseError> {
+        let (key, help) = match keyword {
+            Keyword::Const => ("Const", "Const expression has form 'const x = 5'"),
+            _ => ("Def", "Def expression has form 'def x = 5'"),
+        };
+        self.consume(&Token::Keyword(keyword));
         let name = self.parse_identifier()?;
         if !self.consume(&Token::Equals) {
             // Expect '='
-            return Err(ParseError::new_full(
+            return Err(self.at_current(ParseError::new_full(
                 crate::error::ParseErrorType::LET,
-                &self.source_name,
-                &self.source,
-                (self.current_source().unwrap() + 1, 1).into(),
+                self.source_name,
+                self.source,
+                (self.current_source().unwrap(), 1),
                 "Expected an '='",
-                Some("Def expression has form 'let x = 5'".to_string()),
+                Some(help.to_string()),
                 vec![],
-            ));
+            )));
         }
         let body = self.parse_exp()?;
-        Ok(json!({ "Def": [name, body] }))
+        Ok(json!({ key: [name, body] }))
+    }
+
+    /// Helper function to parse the identifiers of a destructuring pattern, at least one is expected
+    fn parse_pattern(&mut self) -> Result<Value, ParseError> {
+        self.consume(&Token::OpenBracket); // Expect '['
+        let mut identifiers = vec![];
+        loop {
+            identifiers.push(self.parse_identifier()?);
+            self.consume(&Token::Comma); // Optional ',' between identifiers, or a trailing one
+            if self.consume(&Token::CloseBracket) {
+                break;
+            }
+        }
+        Ok(Value::Array(identifiers))
     }
 
     /// Helper function to parse an identifier when it is expected
@@ -270,15 +600,15 @@ impl<'a, T: LexToken> Parser<'a, T> {
                 Ok(ident)
             }
             _ => {
-                return Err(ParseError::new_full(
+                Err(self.at_current(ParseError::new_full(
                     crate::error::ParseErrorType::UNEXPECTED,
-                    &self.source_name,
-                    &self.source,
-                    (self.current_source().unwrap(), 2).into(),
+                    self.source_name,
+                    self.source,
+                    (self.current_source().unwrap(), 2),
                     "Expected an identifier",
                     Some("A valid identifier starts with a valid unicode character, but not a digit, '+' or '-'.".to_string()),
                     vec![],
-                ));
+                )))
             }
         }
     }
@@ -299,4 +629,317 @@ impl<'a, T: LexToken> Parser<'a, T> {
             Ok(ident)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::Diagnostic;
+    use serde_json::json;
+
+    use crate::{error::ParseErrorType, parse, parse_with_spans};
+
+    #[test]
+    fn let_block_value() {
+        let ast = parse("test", "let x = { a; b }").unwrap();
+        assert_eq!(
+            ast,
+            json!({ "Let": [
+                { "Identifier": "x" },
+                { "Block": [{ "Identifier": "a" }, { "Identifier": "b" }] }
+            ]})
+        );
+    }
+
+    #[test]
+    fn trailing_tokens() {
+        assert_eq!(parse("test", "5").unwrap(), json!(5));
+        let err = parse("test", "5 6").unwrap_err();
+        assert!(matches!(err.error_type, ParseErrorType::UNEXPECTED));
+    }
+
+    #[test]
+    fn error_line_and_column() {
+        let err = parse("test", "{\n  add(1, 2);\n  let = 3\n}").unwrap_err();
+        assert_eq!(err.position(), Some((3, 7)));
+        assert_eq!(err.to_string(), "Error while parsing at line 3, column 7!");
+    }
+
+    #[test]
+    fn multibyte_span() {
+        let input = "{ let 名前 = \"é\"; let = 2 }";
+        let err = parse("test", input).unwrap_err();
+
+        // The span is a byte offset, pointing at the '=' after the second 'let'
+        let label = err.labels().unwrap().next().unwrap();
+        assert_eq!(label.offset(), input.find("let = 2").unwrap() + 4);
+        assert_eq!(&input[label.offset()..label.offset() + 1], "=");
+        // Columns still count characters
+        assert_eq!(err.position(), Some((1, 21)));
+    }
+
+    #[test]
+    fn recover_multiple_errors() {
+        let err = parse("test", "{ let = 1; add(1, 2); def = 3 }").unwrap_err();
+        let errors = err.errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].position(), Some((1, 7)));
+        assert_eq!(errors[1].position(), Some((1, 27)));
+
+        // Recovery stops at the end of the input, reporting the missing '}' last
+        let err = parse("test", "{ let = 1; 2").unwrap_err();
+        assert_eq!(err.errors().len(), 2);
+
+        // A single error has no related errors
+        assert_eq!(
+            parse("test", "{ 1; let = 2 }").unwrap_err().errors().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn shebang_program() {
+        let ast = parse("test", "#!/usr/bin/env 417lang\n{ let x = 1; x } // done").unwrap();
+        assert_eq!(
+            ast,
+            json!({ "Block": [
+                { "Let": [{ "Identifier": "x" }, 1] },
+                { "Identifier": "x" }
+            ]})
+        );
+    }
+
+    #[test]
+    fn parse_const() {
+        let ast = parse("test", "{ const x = 1; def y = 2 }").unwrap();
+        assert_eq!(
+            ast,
+            json!({ "Block": [
+                { "Const": [{ "Identifier": "x" }, 1] },
+                { "Def": [{ "Identifier": "y" }, 2] }
+            ]})
+        );
+        assert!(parse("test", "const = 1").is_err());
+    }
+
+    #[test]
+    fn parse_switch() {
+        assert_eq!(
+            parse(
+                "test",
+                "switch n { (1 => \"one\") (2 => \"two\") (else => \"many\") }"
+            )
+            .unwrap(),
+            json!({ "Switch": [
+                { "Identifier": "n" },
+                [{ "Clause": [1, "one"] }, { "Clause": [2, "two"] }],
+                "many"
+            ]})
+        );
+        assert_eq!(
+            parse("test", "switch f(x) { }").unwrap(),
+            json!({ "Switch": [
+                { "Application": [{ "Identifier": "f" }, { "Identifier": "x" }] },
+                []
+            ]})
+        );
+        // The else case must be last, and cases are in parentheses
+        assert!(parse("test", "switch n { (else => 0) (1 => 1) }").is_err());
+        assert!(parse("test", "switch n { 1 => 1 }").is_err());
+        assert!(parse("test", "switch n (1 => 1)").is_err());
+        assert!(parse("test", "else").is_err());
+    }
+
+    #[test]
+    fn parse_try() {
+        let ast = parse("test", "try { div(1, 0) } catch e { e }").unwrap();
+        assert_eq!(
+            ast,
+            json!({ "Try": [
+                { "Block": [{ "Application": [{ "Identifier": "div" }, 1, 0] }] },
+                { "Identifier": "e" },
+                { "Block": [{ "Identifier": "e" }] }
+            ]})
+        );
+        assert!(parse("test", "try { 1 } e { e }").is_err());
+        assert!(parse("test", "catch e { e }").is_err());
+    }
+
+    #[test]
+    fn parse_list_literal() {
+        assert_eq!(parse("test", "[1,2,3]").unwrap(), json!([1, 2, 3]));
+        assert_eq!(
+            parse("test", "[x, [add(1, 2)], []]").unwrap(),
+            json!([
+                { "Identifier": "x" },
+                [{ "Application": [{ "Identifier": "add" }, 1, 2] }],
+                []
+            ])
+        );
+        // Brackets end identifiers like the other delimiters
+        assert_eq!(
+            parse("test", "[a]").unwrap(),
+            json!([{ "Identifier": "a" }])
+        );
+        assert!(parse("test", "[1, 2").is_err());
+    }
+
+    #[test]
+    fn parse_let_in() {
+        assert_eq!(
+            parse("test", "let x = 1 in add(x, 1)").unwrap(),
+            json!({ "LetIn": [
+                { "Identifier": "x" },
+                1,
+                { "Application": [{ "Identifier": "add" }, { "Identifier": "x" }, 1] }
+            ]})
+        );
+        assert!(parse("test", "let x = 1 in").is_err());
+        assert!(parse("test", "in x").is_err());
+    }
+
+    #[test]
+    fn parse_empty_program() {
+        for input in ["", "   ", "\n\t", "// only a comment\n"] {
+            assert_eq!(parse("x", input).unwrap(), json!(null));
+        }
+        assert!(parse("x", "   }").is_err());
+    }
+
+    #[test]
+    fn parse_destructure() {
+        assert_eq!(
+            parse("test", "let [a, b] = pair").unwrap(),
+            json!({ "Destructure": [
+                [{ "Identifier": "a" }, { "Identifier": "b" }],
+                { "Identifier": "pair" }
+            ]})
+        );
+        assert!(parse("test", "let [] = pair").is_err());
+        assert!(parse("test", "let [a, 1] = pair").is_err());
+        assert!(parse("test", "let [a, b] = pair in a").is_err());
+    }
+
+    #[test]
+    fn negative_argument() {
+        assert_eq!(
+            parse("test", "sub(a, -1)").unwrap(),
+            json!({ "Application": [{ "Identifier": "sub" }, { "Identifier": "a" }, -1] })
+        );
+        // Without the comma the '-' follows an operand, so it is not a sign
+        assert!(parse("test", "sub(a -1)").is_err());
+        assert!(parse("test", "- 1").is_err());
+    }
+
+    #[test]
+    fn parse_quote() {
+        assert_eq!(
+            parse("test", "quote(add(1, 2))").unwrap(),
+            json!({ "Quote": { "Application": [{ "Identifier": "add" }, 1, 2] } })
+        );
+        assert!(parse("test", "quote add(1, 2)").is_err());
+        assert!(parse("test", "quote(x").is_err());
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert_eq!(
+            parse("test", "f(a, b,)").unwrap(),
+            parse("test", "f(a, b)").unwrap()
+        );
+        assert_eq!(
+            parse("test", "lambda(x, y,) { x }").unwrap(),
+            parse("test", "lambda(x, y) { x }").unwrap()
+        );
+        assert_eq!(parse("test", "[1, 2,]").unwrap(), json!([1, 2]));
+        // Only after an item
+        assert!(parse("test", "f(,)").is_err());
+        assert!(parse("test", "f(a,,)").is_err());
+    }
+
+    #[test]
+    fn parse_booleans() {
+        assert_eq!(
+            parse("test", "cond (false => 0) (true => 1)").unwrap(),
+            json!({ "Cond": [{ "Clause": [false, 0] }, { "Clause": [true, 1] }] })
+        );
+        assert!(parse("test", "let true = 1").is_err());
+        assert!(parse("test", "{ false = 1 }").is_err());
+    }
+
+    #[test]
+    fn parse_parameter_types() {
+        assert_eq!(
+            parse("test", "lambda(n: integer, xs) { n }").unwrap(),
+            json!({ "Lambda": [
+                { "Parameters": [{ "Identifier": "n", "Type": "integer" }, { "Identifier": "xs" }] },
+                { "Block": [{ "Identifier": "n" }] }
+            ] })
+        );
+        assert!(parse("test", "lambda(n:) { n }").is_err());
+        assert!(parse("test", "lambda(n: 1) { n }").is_err());
+    }
+
+    #[test]
+    fn parse_chars() {
+        assert_eq!(
+            parse("test", "f('a')").unwrap(),
+            json!({ "Application": [{ "Identifier": "f" }, { "Char": "a" }] })
+        );
+        assert!(parse("test", "'ab'").is_err());
+    }
+
+    #[test]
+    fn parse_spans() {
+        assert_eq!(
+            parse_with_spans("test", "add(x, 10) |> f").unwrap(),
+            json!({ "Application": [
+                { "Identifier": "f", "span": [14, 1] },
+                { "Application": [
+                    { "Identifier": "add", "span": [0, 3] },
+                    { "Identifier": "x", "span": [4, 1] },
+                    10
+                ], "span": [0, 10] }
+            ], "span": [0, 15] })
+        );
+        // Offsets are in bytes, and a form spans up to its last token
+        assert_eq!(
+            parse_with_spans("test", "λ(n) { n }").unwrap()["span"],
+            json!([0, 11])
+        );
+        assert_eq!(
+            parse_with_spans("test", "{ def y = \"é\" }").unwrap()["Block"][0]["span"],
+            json!([2, 12])
+        );
+        // Without spans the AST is unchanged
+        assert_eq!(
+            parse("test", "add(x, 10)").unwrap(),
+            json!({ "Application": [{ "Identifier": "add" }, { "Identifier": "x" }, 10] })
+        );
+    }
+
+    #[test]
+    fn parse_pipes() {
+        assert_eq!(
+            parse("test", "5 |> add(1) |> mul(2)").unwrap(),
+            json!({ "Application": [
+                { "Identifier": "mul" },
+                { "Application": [{ "Identifier": "add" }, 5, 1] },
+                2
+            ] })
+        );
+        assert_eq!(
+            parse("test", "xs |> length").unwrap(),
+            parse("test", "length(xs)").unwrap()
+        );
+        // '|>' ends an identifier, and pipes inside arguments and definitions stay there
+        assert_eq!(
+            parse("test", "f(x|>g)").unwrap(),
+            parse("test", "f(g(x))").unwrap()
+        );
+        assert_eq!(
+            parse("test", "def y = 1 |> f").unwrap(),
+            parse("test", "def y = f(1)").unwrap()
+        );
+        assert!(parse("test", "5 |>").is_err());
+    }
+}